@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::receipt::all_receipts;
+
+/// Well-known binaries whose crate is named differently.
+const BUILTIN_ALIASES: &[(&str, &str)] = &[
+    ("btm", "bottom"),
+    ("cargo-flamegraph", "flamegraph"),
+    ("delta", "git-delta"),
+    ("diesel", "diesel_cli"),
+    ("difft", "difftastic"),
+    ("dust", "du-dust"),
+    ("fd", "fd-find"),
+    ("rg", "ripgrep"),
+    ("rga", "ripgrep_all"),
+    ("rip", "rm-improved"),
+    ("sg", "ast-grep"),
+    ("sqlx", "sqlx-cli"),
+    ("svgbob", "svgbob_cli"),
+    ("taplo", "taplo-cli"),
+    ("tldr", "tealdeer"),
+    ("tre", "tre-command"),
+    ("typos", "typos-cli"),
+    ("wasm-bindgen", "wasm-bindgen-cli"),
+    ("wasmtime", "wasmtime-cli"),
+    ("watchexec", "watchexec-cli"),
+];
+
+/// The crate providing the binary `name`, when it is known to differ from `name`.
+///
+/// Aliases from the user's config take precedence over the built-in table, which in turn
+/// takes precedence over what earlier installs recorded in their receipts.
+pub fn resolve_alias(name: &str, config: &Config, install_dir: &Path) -> Result<Option<String>> {
+    if let Some(crate_name) = config.aliases.get(name) {
+        return Ok(Some(crate_name.clone()).filter(|crate_name| crate_name != name));
+    }
+
+    if let Some((_, crate_name)) = BUILTIN_ALIASES.iter().find(|(bin, _)| *bin == name) {
+        return Ok(Some((*crate_name).to_owned()));
+    }
+
+    Ok(all_receipts(install_dir)?
+        .into_iter()
+        .find(|receipt| receipt.binary == name && receipt.crate_name != name)
+        .map(|receipt| receipt.crate_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn builtin_aliases_resolve_common_binaries() {
+        let temp = tempdir().unwrap();
+        let config = Config::default();
+
+        assert_eq!(
+            resolve_alias("rg", &config, temp.path())
+                .unwrap()
+                .as_deref(),
+            Some("ripgrep")
+        );
+        assert_eq!(
+            resolve_alias("fd", &config, temp.path())
+                .unwrap()
+                .as_deref(),
+            Some("fd-find")
+        );
+        assert!(
+            resolve_alias("bat", &config, temp.path())
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn config_aliases_override_builtins() {
+        let temp = tempdir().unwrap();
+        let mut config = Config::default();
+        config.aliases.insert("rg".into(), "rg".into());
+        config.aliases.insert("mytool".into(), "my-tool-cli".into());
+
+        assert!(resolve_alias("rg", &config, temp.path()).unwrap().is_none());
+        assert_eq!(
+            resolve_alias("mytool", &config, temp.path())
+                .unwrap()
+                .as_deref(),
+            Some("my-tool-cli")
+        );
+    }
+
+    #[test]
+    fn builtin_aliases_are_sorted_and_unique() {
+        let names: Vec<&str> = BUILTIN_ALIASES.iter().map(|(bin, _)| *bin).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(names, sorted);
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::load_config;
+use crate::paths::get_cache_dir;
+
+/// How long fetched registry metadata is trusted before it is revalidated.
+const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A registry response kept on disk together with the validators needed to revalidate it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedResponse {
+    /// Seconds since the Unix epoch at which the body was last confirmed current.
+    pub fetched_at: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub body: String,
+}
+
+/// Registry metadata cached under `<cache dir>/metadata`, one JSON file per key.
+pub struct MetadataCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl MetadataCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// The user's metadata cache, with its TTL taken from `CARGOX_CACHE_TTL` or the
+    /// `cache-ttl` config setting.
+    pub fn open() -> Result<Self> {
+        let ttl = match env::var("CARGOX_CACHE_TTL") {
+            Ok(value) => humantime::parse_duration(&value)
+                .map_err(|err| anyhow!("invalid CARGOX_CACHE_TTL `{value}`: {err}"))?,
+            Err(_) => load_config()?.cache_ttl()?.unwrap_or(DEFAULT_TTL),
+        };
+        Ok(Self::new(get_cache_dir()?.join("metadata"), ttl))
+    }
+
+    /// The same cache, but with every entry revalidated before it is used.
+    pub fn revalidating(&self) -> Self {
+        Self::new(self.dir.clone(), Duration::ZERO)
+    }
+
+    /// The cached response for `key`, if there is one. Unreadable entries are ignored.
+    pub fn get(&self, key: &str) -> Option<CachedResponse> {
+        let contents = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Whether `entry` is recent enough to use without asking the registry.
+    pub fn is_fresh(&self, entry: &CachedResponse, now: u64) -> bool {
+        now.saturating_sub(entry.fetched_at) < self.ttl.as_secs()
+    }
+
+    /// Store `entry` for `key`, replacing any previous entry atomically so concurrent
+    /// runs never observe a partially written file.
+    pub fn put(&self, key: &str, entry: &CachedResponse) -> Result<()> {
+        let path = self.path(key);
+        let parent = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+
+        let contents = serde_json::to_string(entry).context("failed to serialize cache entry")?;
+        let mut file = tempfile::NamedTempFile::new_in(parent).with_context(|| {
+            format!("failed to create a temporary file in {}", parent.display())
+        })?;
+        file.write_all(contents.as_bytes())
+            .context("failed to write cache entry")?;
+        file.persist(&path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(fetched_at: u64) -> CachedResponse {
+        CachedResponse {
+            fetched_at,
+            etag: Some("\"abc\"".into()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+            body: "{\"versions\":[]}".into(),
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let temp = tempdir().unwrap();
+        let cache = MetadataCache::new(temp.path().to_path_buf(), DEFAULT_TTL);
+
+        assert!(cache.get("crates.io/ripgrep").is_none());
+        cache.put("crates.io/ripgrep", &entry(100)).unwrap();
+        assert_eq!(cache.get("crates.io/ripgrep"), Some(entry(100)));
+        assert!(temp.path().join("crates.io/ripgrep.json").is_file());
+    }
+
+    #[test]
+    fn freshness_follows_ttl() {
+        let temp = tempdir().unwrap();
+        let cache = MetadataCache::new(temp.path().to_path_buf(), Duration::from_secs(60));
+        assert!(cache.is_fresh(&entry(1_000), 1_059));
+        assert!(!cache.is_fresh(&entry(1_000), 1_060));
+
+        let always_revalidate = MetadataCache::new(temp.path().to_path_buf(), Duration::ZERO);
+        assert!(!always_revalidate.is_fresh(&entry(1_000), 1_000));
+        assert!(!cache.revalidating().is_fresh(&entry(1_000), 1_000));
+    }
+
+    #[test]
+    fn corrupt_entries_are_ignored() {
+        let temp = tempdir().unwrap();
+        let cache = MetadataCache::new(temp.path().to_path_buf(), DEFAULT_TTL);
+        fs::write(temp.path().join("broken.json"), "not json").unwrap();
+        assert!(cache.get("broken").is_none());
+    }
+}
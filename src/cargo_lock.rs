@@ -0,0 +1,193 @@
+use anyhow::{Context, Result, anyhow};
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Tools whose version must track a library the project depends on.
+const LOCKSTEP_TOOLS: &[(&str, &str, Lockstep)] = &[
+    ("diesel_cli", "diesel", Lockstep::Minor),
+    ("sqlx-cli", "sqlx", Lockstep::Exact),
+    ("uniffi-bindgen", "uniffi", Lockstep::Minor),
+    ("wasm-bindgen-cli", "wasm-bindgen", Lockstep::Exact),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Lockstep {
+    /// The tool must be exactly the library's version.
+    Exact,
+    /// The tool must share the library's major and minor version.
+    Minor,
+}
+
+#[derive(Deserialize)]
+struct CargoLock {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+}
+
+/// The nearest `Cargo.lock` in `start` or its parents.
+pub fn find_cargo_lock(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+}
+
+/// An exact requirement for the version of `package` in the nearest `Cargo.lock`.
+pub fn dependency_requirement(start: &Path, package: &str) -> Result<VersionReq> {
+    let lock_path = find_cargo_lock(start)
+        .ok_or_else(|| anyhow!("no Cargo.lock found in {} or its parents", start.display()))?;
+    let version = single_locked_version(&lock_path, package)?.ok_or_else(|| {
+        anyhow!(
+            "{} does not contain the package {package}",
+            lock_path.display()
+        )
+    })?;
+    Ok(requirement_for(&version, Lockstep::Exact))
+}
+
+/// The requirement implied by the built-in rule table when `tool` must track a library
+/// that the project in `start` depends on.
+pub fn lockstep_requirement(start: &Path, tool: &str) -> Result<Option<VersionReq>> {
+    let Some((_, library, lockstep)) = LOCKSTEP_TOOLS.iter().find(|(name, _, _)| *name == tool)
+    else {
+        return Ok(None);
+    };
+    let Some(lock_path) = find_cargo_lock(start) else {
+        return Ok(None);
+    };
+
+    Ok(single_locked_version(&lock_path, library)?
+        .map(|version| requirement_for(&version, *lockstep)))
+}
+
+fn single_locked_version(lock_path: &Path, package: &str) -> Result<Option<Version>> {
+    let mut versions = locked_versions(lock_path, package)?;
+    match versions.len() {
+        0 => Ok(None),
+        1 => Ok(versions.pop()),
+        _ => {
+            let listed: Vec<String> = versions.iter().map(Version::to_string).collect();
+            Err(anyhow!(
+                "{} contains several versions of {package} ({}); specify the tool version explicitly",
+                lock_path.display(),
+                listed.join(", ")
+            ))
+        }
+    }
+}
+
+fn locked_versions(lock_path: &Path, package: &str) -> Result<Vec<Version>> {
+    let contents = fs::read_to_string(lock_path)
+        .with_context(|| format!("failed to read {}", lock_path.display()))?;
+    let lock: CargoLock = toml::from_str(&contents)
+        .with_context(|| format!("failed to parse {}", lock_path.display()))?;
+
+    let mut versions: Vec<Version> = lock
+        .package
+        .into_iter()
+        .filter(|entry| entry.name == package)
+        .filter_map(|entry| Version::parse(&entry.version).ok())
+        .collect();
+    versions.sort();
+    versions.dedup();
+    Ok(versions)
+}
+
+fn requirement_for(version: &Version, lockstep: Lockstep) -> VersionReq {
+    let requirement = match lockstep {
+        Lockstep::Exact => format!("={version}"),
+        Lockstep::Minor => format!("~{}.{}", version.major, version.minor),
+    };
+    VersionReq::parse(&requirement).expect("requirement built from a valid version")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const LOCK: &str = r#"
+version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+
+[[package]]
+name = "wasm-bindgen"
+version = "0.2.92"
+
+[[package]]
+name = "diesel"
+version = "2.2.4"
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+
+[[package]]
+name = "syn"
+version = "2.0.60"
+"#;
+
+    fn project() -> tempfile::TempDir {
+        let temp = tempdir().unwrap();
+        fs::write(temp.path().join("Cargo.lock"), LOCK).unwrap();
+        fs::create_dir_all(temp.path().join("src")).unwrap();
+        temp
+    }
+
+    #[test]
+    fn dependency_requirement_is_exact() {
+        let temp = project();
+        let req = dependency_requirement(&temp.path().join("src"), "wasm-bindgen").unwrap();
+        assert_eq!(req.to_string(), "=0.2.92");
+    }
+
+    #[test]
+    fn dependency_requirement_reports_missing_and_ambiguous_packages() {
+        let temp = project();
+        let missing = dependency_requirement(temp.path(), "serde").unwrap_err();
+        assert!(
+            missing
+                .to_string()
+                .contains("does not contain the package serde")
+        );
+
+        let ambiguous = dependency_requirement(temp.path(), "syn").unwrap_err();
+        assert!(ambiguous.to_string().contains("1.0.109, 2.0.60"));
+    }
+
+    #[test]
+    fn lockstep_requirement_uses_rule_table() {
+        let temp = project();
+        let req = lockstep_requirement(temp.path(), "wasm-bindgen-cli")
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.to_string(), "=0.2.92");
+
+        let req = lockstep_requirement(temp.path(), "diesel_cli")
+            .unwrap()
+            .unwrap();
+        assert_eq!(req.to_string(), "~2.2");
+
+        assert!(
+            lockstep_requirement(temp.path(), "sqlx-cli")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            lockstep_requirement(temp.path(), "ripgrep")
+                .unwrap()
+                .is_none()
+        );
+    }
+}
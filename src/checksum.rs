@@ -0,0 +1,141 @@
+use anyhow::{Context, Result, anyhow};
+use semver::Version;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Check the `.crate` files cargo downloaded for `crate_name` at `version` from the index
+/// at `index_url` against the `checksum` the index lists. Cargo keeps downloads in
+/// `registry/cache/<host>-<hash>/` under its home, with a directory per registry and per
+/// hashing scheme of past cargo releases, so every copy found is checked. Returns whether
+/// any copy was found.
+pub fn verify_downloaded_crate(
+    cargo_home: &Path,
+    index_url: &str,
+    crate_name: &str,
+    version: &Version,
+    checksum: &str,
+) -> Result<bool> {
+    let copies = downloaded_crates(cargo_home, index_url, &format!("{crate_name}-{version}"))?;
+    for copy in &copies {
+        let actual = sha256_file(copy)?;
+        if !actual.eq_ignore_ascii_case(checksum) {
+            return Err(anyhow!(
+                "{} has checksum {actual}, but the registry lists {checksum} for {crate_name}@{version}; \
+                 delete it and install again",
+                copy.display()
+            ));
+        }
+    }
+    Ok(!copies.is_empty())
+}
+
+fn downloaded_crates(cargo_home: &Path, index_url: &str, package: &str) -> Result<Vec<PathBuf>> {
+    let cache_dir = cargo_home.join("registry").join("cache");
+    let entries = match fs::read_dir(&cache_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).context(format!("failed to read {}", cache_dir.display())),
+    };
+
+    let host = index_host(index_url);
+    let mut copies = vec![];
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", cache_dir.display()))?;
+        let name = entry.file_name();
+        let is_registry = name
+            .to_string_lossy()
+            .rsplit_once('-')
+            .is_some_and(|(dir_host, _)| dir_host == host);
+        let copy = entry.path().join(format!("{package}.crate"));
+        if is_registry && copy.is_file() {
+            copies.push(copy);
+        }
+    }
+    Ok(copies)
+}
+
+/// The host of an index URL, which cargo names its cache directories after.
+fn index_host(index_url: &str) -> &str {
+    let url = index_url.strip_prefix("sparse+").unwrap_or(index_url);
+    let authority = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = authority.split('/').next().unwrap_or(authority);
+    authority.split(':').next().unwrap_or(authority)
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let contents = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(format!("{:x}", Sha256::digest(&contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn index_host_strips_scheme_path_and_port() {
+        assert_eq!(index_host("https://index.crates.io"), "index.crates.io");
+        assert_eq!(
+            index_host("sparse+https://registry.example.com:8443/index/"),
+            "registry.example.com"
+        );
+    }
+
+    #[test]
+    fn verify_downloaded_crate_checks_every_copy() {
+        let cargo_home = tempdir().unwrap();
+        let version = Version::new(14, 1, 0);
+        let contents = b"crate contents";
+        let checksum = format!("{:x}", Sha256::digest(contents));
+        let cache = cargo_home.path().join("registry/cache");
+
+        let found = verify_downloaded_crate(
+            cargo_home.path(),
+            "https://index.crates.io",
+            "ripgrep",
+            &version,
+            &checksum,
+        )
+        .unwrap();
+        assert!(!found);
+
+        write(
+            &cache.join("index.crates.io-1949cf8c6b5b557f/ripgrep-14.1.0.crate"),
+            contents,
+        );
+        // Downloads from other registries are not compared.
+        write(
+            &cache.join("registry.example.com-0123456789abcdef/ripgrep-14.1.0.crate"),
+            b"something else",
+        );
+        let found = verify_downloaded_crate(
+            cargo_home.path(),
+            "https://index.crates.io",
+            "ripgrep",
+            &version,
+            &checksum,
+        )
+        .unwrap();
+        assert!(found);
+
+        write(
+            &cache.join("index.crates.io-6f17d22bba15001f/ripgrep-14.1.0.crate"),
+            b"tampered",
+        );
+        let err = verify_downloaded_crate(
+            cargo_home.path(),
+            "https://index.crates.io",
+            "ripgrep",
+            &version,
+            &checksum,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("but the registry lists"), "{err}");
+    }
+}
@@ -1,20 +1,38 @@
 use anyhow::{Result, anyhow};
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use std::env;
 use std::ffi::OsString;
+use std::path::PathBuf;
 
 /// Run Cargo binaries on demand
 #[derive(Parser, Debug)]
-#[command(name = "cargox", author, version, about = "Run Cargo binaries on demand", long_about = None, arg_required_else_help = true)]
+#[command(
+    name = "cargox",
+    author,
+    version,
+    about = "Run Cargo binaries on demand",
+    long_about = None,
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    disable_help_subcommand = true
+)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Crate to run, optionally suffixed with `@version`
-    #[arg(value_name = "crate[@version]")]
-    pub crate_spec: String,
+    #[arg(value_name = "crate[@version]", required = true)]
+    pub crate_spec: Option<String>,
 
-    /// Execute this binary from the crate (defaults to crate name)
+    /// Execute this binary from the crate (defaults to its only binary, or the crate name)
     #[arg(long, value_name = "NAME")]
     pub bin: Option<String>,
 
+    /// Resolve and install the crate from this registry instead of crates.io
+    #[arg(long, value_name = "NAME")]
+    pub registry: Option<String>,
+
     /// Force reinstall; ignore any existing binary
     #[arg(short, long)]
     pub force: bool,
@@ -27,11 +45,84 @@ pub struct Cli {
     #[arg(short = 's', long)]
     pub build_from_source: bool,
 
+    /// Never contact the network; only run versions that are already installed (also set by
+    /// `CARGOX_OFFLINE`, which `--force` overrides)
+    #[arg(long, conflicts_with = "force")]
+    pub offline: bool,
+
+    /// Check a newly installed binary before using it: `header` checks that it is an
+    /// executable for this platform, `version` also checks what `--version` reports
+    #[arg(
+        long,
+        env = "CARGOX_VERIFY",
+        value_name = "CHECK",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "header"
+    )]
+    pub verify: Option<Verification>,
+
     /// Arguments passed to the executed binary (use `--` to delimit)
     #[arg(trailing_var_arg = true, value_name = "binary-args")]
     pub args: Vec<OsString>,
 }
 
+/// How much of a newly installed binary `--verify` checks
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The file is an executable for this platform's format, architecture and libc
+    Header,
+    /// The header checks, and `--version` reports the version that was installed
+    Version,
+}
+
+/// Commands for managing the binaries cargox has installed
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List every versioned binary installed by cargox
+    List,
+    /// Remove installed versions of a crate (all versions unless one is given)
+    Uninstall {
+        /// Crate to remove, optionally suffixed with `@version`
+        #[arg(value_name = "crate[@version]")]
+        crate_spec: String,
+    },
+    /// Remove old versions according to a retention policy
+    Gc {
+        /// Keep the newest N versions of each binary (defaults to 1 when no policy is given)
+        #[arg(long, value_name = "N")]
+        keep: Option<usize>,
+
+        /// Remove versions that have not been run in the last DAYS days
+        #[arg(long, value_name = "DAYS")]
+        unused_for: Option<u64>,
+
+        /// Print what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Re-resolve the versions locked in the project's cargox.lock
+    Update {
+        /// Only update this tool (defaults to every tool in the manifest)
+        #[arg(value_name = "TOOL")]
+        tool: Option<String>,
+    },
+    /// Build and run a single-file Rust script with an embedded `---cargo` manifest
+    Run {
+        /// The script to run
+        #[arg(value_name = "SCRIPT")]
+        script: PathBuf,
+
+        /// Arguments passed to the script
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "script-args"
+        )]
+        args: Vec<OsString>,
+    },
+}
+
 impl Cli {
     /// Parse arguments, ensuring that arguments after the crate spec are passed to the binary
     /// rather than being intercepted by clap. This allows `cargox bat --help` to show bat's
@@ -44,6 +135,7 @@ impl Cli {
             return Err(anyhow!("no program name in arguments"));
         }
         args.remove(0);
+        let args = split_shebang_args(args)?;
 
         // Find the first positional argument (crate spec) by iterating through args
         // and stopping at the first argument that doesn't start with `-` and isn't a value for a flag
@@ -61,13 +153,13 @@ impl Cli {
             let arg = args[i].to_string_lossy();
 
             // Check if this is a flag that takes a value
-            if arg == "--bin" {
+            if arg == "--bin" || arg == "--registry" {
                 skip_next = true;
                 i += 1;
                 continue;
             }
 
-            // If it doesn't start with `-`, it's the crate spec
+            // If it doesn't start with `-`, it's the crate spec (or a cargox subcommand)
             if !arg.starts_with('-') {
                 crate_spec_idx = Some(i);
                 break;
@@ -76,8 +168,24 @@ impl Cli {
             i += 1;
         }
 
-        // If we found a crate spec, split args at that point
-        let (cargox_args, binary_args) = if let Some(idx) = crate_spec_idx {
+        // Subcommands parse all of their own arguments, except that everything after the
+        // script given to `run` belongs to the script
+        let subcommand = crate_spec_idx
+            .map(|idx| args[idx].to_string_lossy().into_owned())
+            .filter(|name| is_subcommand(name));
+        let script_idx = crate_spec_idx.map(|idx| idx + 1).filter(|&idx| {
+            subcommand.as_deref() == Some("run")
+                && args
+                    .get(idx)
+                    .is_some_and(|arg| !arg.to_string_lossy().starts_with('-'))
+        });
+
+        // If we found a crate spec or script, split args at that point
+        let (cargox_args, binary_args) = if let Some(idx) = script_idx {
+            (args[..=idx].to_vec(), args[idx + 1..].to_vec())
+        } else if let Some(idx) = crate_spec_idx
+            && subcommand.is_none()
+        {
             let mut cargox_args = args[..idx].to_vec();
             // Add the crate spec to cargox args
             cargox_args.push(args[idx].clone());
@@ -100,13 +208,98 @@ impl Cli {
                 }
             };
 
-        // Set the binary arguments
-        cli.args = binary_args;
+        // Set the binary (or script) arguments
+        match &mut cli.command {
+            Some(Command::Run { args, .. }) => *args = binary_args,
+            _ => cli.args = binary_args,
+        }
+
+        // Read after parsing rather than through clap so that an explicit `--force` wins over
+        // an offline environment instead of conflicting with it
+        if !cli.force && env::var("CARGOX_OFFLINE").is_ok_and(|value| is_truthy(&value)) {
+            cli.offline = true;
+        }
 
         Ok(cli)
     }
 }
 
+/// Whether an environment flag is set, accepting the same values as clap's boolish parser.
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes" | "t" | "true" | "on" | "1"
+    )
+}
+
+/// Marks a `#!` line that names cargox as the interpreter directly.
+const SHEBANG_FLAG: &str = "--shebang";
+
+/// Split the arguments of a `#!` line that starts with `--shebang`. The kernel passes
+/// everything after the interpreter on a `#!` line as a single argument, so on Linux
+/// `#!/usr/local/bin/cargox --shebang -q tokei@12` runs cargox with `--shebang -q tokei@12`
+/// followed by the script's path. Only a first argument that starts with the flag is split,
+/// since others can hold whitespace of their own, such as a `path:` spec. A `--shebang`
+/// that arrives on its own, as from `env -S`, is dropped.
+///
+/// Words are split as `env -S` splits them: on whitespace, with single and double quotes
+/// grouping words and a backslash escaping the next character outside single quotes.
+fn split_shebang_args(mut args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let Some(first) = args.first().and_then(|arg| arg.to_str()) else {
+        return Ok(args);
+    };
+    if first == SHEBANG_FLAG {
+        args.remove(0);
+        return Ok(args);
+    }
+    let Some(line) = first
+        .strip_prefix(SHEBANG_FLAG)
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+    else {
+        return Ok(args);
+    };
+
+    let words = split_words(line)?;
+    args.splice(..1, words.into_iter().map(OsString::from));
+    Ok(args)
+}
+
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_default();
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (Some('\''), c) => word.get_or_insert_default().push(c),
+            (_, '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("trailing backslash in `{line}`"))?;
+                word.get_or_insert_default().push(escaped);
+            }
+            (_, c) => word.get_or_insert_default().push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow!("unterminated quote in `{line}`"));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn is_subcommand(name: &str) -> bool {
+    Cli::command().find_subcommand(name).is_some()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,15 +319,162 @@ mod tests {
     #[test]
     fn parse_args_handles_bin_flag() {
         let cli = Cli::try_parse_from(["cargox", "--bin", "foo", "mycrate"]).unwrap();
-        assert_eq!(cli.crate_spec, "mycrate");
+        assert_eq!(cli.crate_spec.as_deref(), Some("mycrate"));
         assert_eq!(cli.bin, Some("foo".to_string()));
         assert_eq!(cli.args.len(), 0);
     }
 
+    #[test]
+    fn parse_args_handles_registry_flag() {
+        let cli = Cli::try_parse_from(["cargox", "--registry", "internal", "tool@1.2"]).unwrap();
+        assert_eq!(cli.crate_spec.as_deref(), Some("tool@1.2"));
+        assert_eq!(cli.registry.as_deref(), Some("internal"));
+    }
+
     #[test]
     fn parse_args_handles_force_flag() {
         let cli = Cli::try_parse_from(["cargox", "-f", "mycrate"]).unwrap();
-        assert_eq!(cli.crate_spec, "mycrate");
+        assert_eq!(cli.crate_spec.as_deref(), Some("mycrate"));
         assert!(cli.force);
     }
+
+    #[test]
+    fn parse_args_handles_offline_flag() {
+        let cli = Cli::try_parse_from(["cargox", "--offline", "mycrate@1"]).unwrap();
+        assert_eq!(cli.crate_spec.as_deref(), Some("mycrate@1"));
+        assert!(cli.offline);
+    }
+
+    #[test]
+    fn offline_env_values_are_boolish() {
+        assert!(is_truthy("1"));
+        assert!(is_truthy("True"));
+        assert!(is_truthy("yes"));
+        assert!(!is_truthy("0"));
+        assert!(!is_truthy("off"));
+        assert!(!is_truthy(""));
+    }
+
+    #[test]
+    fn parse_args_handles_verify_flag() {
+        let cli = Cli::try_parse_from(["cargox", "--verify", "mycrate"]).unwrap();
+        assert_eq!(cli.crate_spec.as_deref(), Some("mycrate"));
+        assert_eq!(cli.verify, Some(Verification::Header));
+
+        let cli = Cli::try_parse_from(["cargox", "--verify=version", "mycrate"]).unwrap();
+        assert_eq!(cli.verify, Some(Verification::Version));
+    }
+
+    #[test]
+    fn parse_args_handles_list_subcommand() {
+        let cli = Cli::try_parse_from(["cargox", "list"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::List)));
+        assert!(cli.crate_spec.is_none());
+    }
+
+    #[test]
+    fn parse_args_handles_uninstall_subcommand() {
+        let cli = Cli::try_parse_from(["cargox", "uninstall", "ripgrep@14"]).unwrap();
+        match cli.command {
+            Some(Command::Uninstall { crate_spec }) => assert_eq!(crate_spec, "ripgrep@14"),
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_args_handles_gc_subcommand() {
+        let cli = Cli::try_parse_from([
+            "cargox",
+            "gc",
+            "--keep",
+            "2",
+            "--unused-for",
+            "30",
+            "--dry-run",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Gc {
+                keep,
+                unused_for,
+                dry_run,
+            }) => {
+                assert_eq!(keep, Some(2));
+                assert_eq!(unused_for, Some(30));
+                assert!(dry_run);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_args_handles_update_subcommand() {
+        let cli = Cli::try_parse_from(["cargox", "update", "cargo-deny"]).unwrap();
+        match cli.command {
+            Some(Command::Update { tool }) => assert_eq!(tool.as_deref(), Some("cargo-deny")),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        let cli = Cli::try_parse_from(["cargox", "update"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Update { tool: None })));
+    }
+
+    #[test]
+    fn parse_args_passes_flags_through_to_scripts() {
+        let cli =
+            Cli::try_parse_from(["cargox", "run", "tidy.rs", "file", "--help", "-q"]).unwrap();
+        match cli.command {
+            Some(Command::Run { script, args }) => {
+                assert_eq!(script, PathBuf::from("tidy.rs"));
+                assert_eq!(args, ["file", "--help", "-q"]);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn shebang_args_are_split_like_env() {
+        let args =
+            split_shebang_args(os_args(&["--shebang -q tokei@12", "./count.sh", "a b"])).unwrap();
+        assert_eq!(args, os_args(&["-q", "tokei@12", "./count.sh", "a b"]));
+
+        let args =
+            split_shebang_args(os_args(&["--shebang", "tokei@12", "./count.sh", "a b"])).unwrap();
+        assert_eq!(args, os_args(&["tokei@12", "./count.sh", "a b"]));
+
+        let args = split_shebang_args(os_args(&["tokei@12", "./count.sh", "a b"])).unwrap();
+        assert_eq!(args, os_args(&["tokei@12", "./count.sh", "a b"]));
+
+        let args = split_shebang_args(os_args(&[
+            r#"--shebang tool --name "two words" 'it''s' a\ b """#,
+            "script",
+        ]))
+        .unwrap();
+        assert_eq!(
+            args,
+            os_args(&["tool", "--name", "two words", "its", "a b", "", "script"])
+        );
+
+        assert!(split_shebang_args(os_args(&["--shebang tool 'open"])).is_err());
+    }
+
+    #[test]
+    fn specs_with_spaces_are_not_split() {
+        let args =
+            split_shebang_args(os_args(&["path:./my tools/codegen", "--out", "a b"])).unwrap();
+        assert_eq!(args, os_args(&["path:./my tools/codegen", "--out", "a b"]));
+
+        let args = split_shebang_args(os_args(&["--shebangs are fine"])).unwrap();
+        assert_eq!(args, os_args(&["--shebangs are fine"]));
+    }
+
+    #[test]
+    fn subcommand_names_are_recognized() {
+        assert!(is_subcommand("list"));
+        assert!(!is_subcommand("bat"));
+    }
 }
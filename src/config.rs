@@ -0,0 +1,137 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::paths::get_config_path;
+
+/// User configuration read from `config.toml` in the cargox config directory.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Binary names mapped to the crate that provides them, e.g. `rg = "ripgrep"`.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+    /// How long registry metadata is cached before revalidation, e.g. `"10m"`.
+    pub cache_ttl: Option<String>,
+    /// Registries cargox can resolve and install from, in addition to those in Cargo's
+    /// own configuration.
+    #[serde(default)]
+    pub registries: BTreeMap<String, RegistryConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RegistryConfig {
+    /// The registry's index, e.g. `sparse+https://my-registry.example.com/index/`.
+    pub index: String,
+    /// The Cargo credential provider that supplies the registry's token.
+    pub credential_provider: Option<ProviderCommand>,
+}
+
+/// A credential provider command, written either as a string split on whitespace or as
+/// an array of arguments, like Cargo's `credential-provider`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ProviderCommand {
+    Command(String),
+    Args(Vec<String>),
+}
+
+impl ProviderCommand {
+    pub fn to_args(&self) -> Vec<String> {
+        match self {
+            ProviderCommand::Command(command) => {
+                command.split_whitespace().map(str::to_owned).collect()
+            }
+            ProviderCommand::Args(args) => args.clone(),
+        }
+    }
+}
+
+impl Config {
+    pub fn cache_ttl(&self) -> Result<Option<Duration>> {
+        self.cache_ttl
+            .as_deref()
+            .map(|ttl| {
+                humantime::parse_duration(ttl)
+                    .map_err(|err| anyhow!("invalid cache-ttl `{ttl}`: {err}"))
+            })
+            .transpose()
+    }
+}
+
+pub fn load_config() -> Result<Config> {
+    match get_config_path() {
+        Some(path) => load_config_from(&path),
+        None => Ok(Config::default()),
+    }
+}
+
+fn load_config_from(path: &Path) -> Result<Config> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+    };
+
+    toml::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn load_config_reads_aliases() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "[aliases]\nrg = \"ripgrep\"\n").unwrap();
+
+        let config = load_config_from(&path).unwrap();
+        assert_eq!(
+            config.aliases.get("rg").map(String::as_str),
+            Some("ripgrep")
+        );
+    }
+
+    #[test]
+    fn load_config_reads_cache_ttl() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(&path, "cache-ttl = \"10m\"\n").unwrap();
+
+        let config = load_config_from(&path).unwrap();
+        assert_eq!(config.cache_ttl().unwrap(), Some(Duration::from_secs(600)));
+
+        fs::write(&path, "cache-ttl = \"soon\"\n").unwrap();
+        assert!(load_config_from(&path).unwrap().cache_ttl().is_err());
+    }
+
+    #[test]
+    fn load_config_reads_registries() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(
+            &path,
+            "[registries.internal]\nindex = \"sparse+https://registry.example.com/index/\"\n",
+        )
+        .unwrap();
+
+        let config = load_config_from(&path).unwrap();
+        assert_eq!(
+            config.registries["internal"].index,
+            "sparse+https://registry.example.com/index/"
+        );
+    }
+
+    #[test]
+    fn load_config_defaults_when_missing() {
+        let temp = tempdir().unwrap();
+        let config = load_config_from(&temp.path().join("config.toml")).unwrap();
+        assert!(config.aliases.is_empty());
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::paths::cargo_home;
+use crate::registries::{AltRegistry, registry_env_var};
+
+/// Obtain a token for `registry` the way Cargo does: from `CARGO_REGISTRIES_<NAME>_TOKEN`,
+/// then from each configured credential provider in order of precedence.
+pub fn registry_token(registry: &AltRegistry) -> Result<String> {
+    let env_var = registry_env_var(&registry.name, "TOKEN");
+    if let Ok(token) = env::var(&env_var) {
+        return Ok(token);
+    }
+
+    for provider in &registry.credential_providers {
+        if let Some(token) = provider_token(registry, provider)? {
+            return Ok(token);
+        }
+    }
+
+    Err(anyhow!(
+        "registry `{name}` requires authentication, but no token was found; run `cargo login --registry {name}` or set {env_var}",
+        name = registry.name
+    ))
+}
+
+fn provider_token(registry: &AltRegistry, provider: &[String]) -> Result<Option<String>> {
+    let Some((program, args)) = provider.split_first() else {
+        return Ok(None);
+    };
+
+    match program.as_str() {
+        "cargo:token" => match cargo_home() {
+            Some(cargo_home) => credentials_file_token(&cargo_home, &registry.name),
+            None => Ok(None),
+        },
+        "cargo:token-from-stdout" => token_from_stdout(registry, args).map(Some),
+        "cargo:wincred" | "cargo:macos-keychain" | "cargo:libsecret" => {
+            eprintln!(
+                "warning: the {program} credential provider is built into Cargo and cannot be used by cargox; skipping it"
+            );
+            Ok(None)
+        }
+        builtin if builtin.starts_with("cargo:") => {
+            Err(anyhow!("unknown credential provider `{builtin}`"))
+        }
+        _ => plugin_token(registry, program, args),
+    }
+}
+
+/// The token `cargo login` stored for the registry `name`.
+fn credentials_file_token(cargo_home: &Path, name: &str) -> Result<Option<String>> {
+    for file in ["credentials.toml", "credentials"] {
+        let path = cargo_home.join(file);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+        };
+        let table: toml::Table = contents
+            .parse()
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        let token = table
+            .get("registries")
+            .and_then(|registries| registries.get(name))
+            .and_then(|registry| registry.get("token"))
+            .and_then(|token| token.as_str());
+        if let Some(token) = token {
+            return Ok(Some(token.to_owned()));
+        }
+    }
+    Ok(None)
+}
+
+/// `cargo:token-from-stdout <command> [args…]`: the token is the first line the command
+/// prints.
+fn token_from_stdout(registry: &AltRegistry, command: &[String]) -> Result<String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("cargo:token-from-stdout requires a command to run"))?;
+
+    let output = Command::new(program)
+        .args(args)
+        .env("CARGO_REGISTRY_INDEX_URL", index_url(registry))
+        .env("CARGO_REGISTRY_NAME_OPT", &registry.name)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run credential provider `{program}`"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "credential provider `{program}` exited with {}",
+            output.status
+        ));
+    }
+
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("credential provider `{program}` printed invalid UTF-8"))?;
+    let token = stdout.lines().next().unwrap_or_default().trim();
+    if token.is_empty() {
+        return Err(anyhow!(
+            "credential provider `{program}` did not print a token"
+        ));
+    }
+    Ok(token.to_owned())
+}
+
+#[derive(Deserialize)]
+struct PluginHello {
+    v: Vec<u32>,
+}
+
+#[derive(Serialize)]
+struct PluginRequest<'a> {
+    v: u32,
+    registry: PluginRegistry<'a>,
+    kind: &'static str,
+    operation: &'static str,
+    args: &'a [String],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PluginRegistry<'a> {
+    index_url: String,
+    name: &'a str,
+}
+
+#[derive(Deserialize)]
+enum PluginResponse {
+    Ok(PluginToken),
+    Err(PluginError),
+}
+
+#[derive(Deserialize)]
+struct PluginToken {
+    token: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PluginError {
+    kind: String,
+    message: Option<String>,
+    #[serde(default)]
+    caused_by: Vec<String>,
+}
+
+/// Ask an external credential provider for a token using version 1 of Cargo's credential
+/// provider protocol: the provider greets with the versions it speaks, answers a single
+/// `get` request, and exits once its stdin is closed.
+fn plugin_token(registry: &AltRegistry, program: &str, args: &[String]) -> Result<Option<String>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .arg("--cargo-plugin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("failed to run credential provider `{program}`"))?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut read_message = |what: &str| -> Result<String> {
+        let mut line = String::new();
+        stdout.read_line(&mut line).with_context(|| {
+            format!("failed to read {what} from credential provider `{program}`")
+        })?;
+        if line.trim().is_empty() {
+            return Err(anyhow!(
+                "credential provider `{program}` exited without sending {what}"
+            ));
+        }
+        Ok(line)
+    };
+
+    let hello: PluginHello = serde_json::from_str(&read_message("a greeting")?)
+        .with_context(|| format!("credential provider `{program}` sent an invalid greeting"))?;
+    if !hello.v.contains(&1) {
+        return Err(anyhow!(
+            "credential provider `{program}` does not support protocol version 1"
+        ));
+    }
+
+    let request = PluginRequest {
+        v: 1,
+        registry: PluginRegistry {
+            index_url: index_url(registry),
+            name: &registry.name,
+        },
+        kind: "get",
+        operation: "read",
+        args,
+    };
+    let mut request = serde_json::to_string(&request).context("failed to serialize request")?;
+    request.push('\n');
+    stdin
+        .write_all(request.as_bytes())
+        .with_context(|| format!("failed to send request to credential provider `{program}`"))?;
+
+    let response = read_message("a response");
+    drop(stdin);
+    let _ = child.wait();
+
+    let response: PluginResponse = serde_json::from_str(&response?)
+        .with_context(|| format!("credential provider `{program}` sent an invalid response"))?;
+    match response {
+        PluginResponse::Ok(token) => Ok(Some(token.token)),
+        PluginResponse::Err(err) if err.kind == "not-found" || err.kind == "url-not-supported" => {
+            Ok(None)
+        }
+        PluginResponse::Err(err) => {
+            let mut message = err.message.unwrap_or(err.kind);
+            for cause in err.caused_by {
+                message.push_str(&format!(": {cause}"));
+            }
+            Err(anyhow!("credential provider `{program}` failed: {message}"))
+        }
+    }
+}
+
+/// The index URL as Cargo hands it to credential providers, including the `sparse+` prefix.
+fn index_url(registry: &AltRegistry) -> String {
+    format!("sparse+{}/", registry.index_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    fn registry(providers: Vec<Vec<String>>) -> AltRegistry {
+        AltRegistry {
+            name: "internal".into(),
+            index_url: "https://registry.example.com/index".into(),
+            defined_in: crate::registries::DefinedIn::Cargo,
+            credential_providers: providers,
+        }
+    }
+
+    #[cfg(unix)]
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn credentials_file_supplies_tokens() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("credentials.toml"),
+            "[registry]\ntoken = \"crates-io\"\n\n[registries.internal]\ntoken = \"secret\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            credentials_file_token(temp.path(), "internal").unwrap(),
+            Some("secret".to_owned())
+        );
+        assert_eq!(credentials_file_token(temp.path(), "other").unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_from_stdout_reads_first_line() {
+        let registry = registry(vec![]);
+        let token = token_from_stdout(
+            &registry,
+            &args(&[
+                "sh",
+                "-c",
+                "echo \"$CARGO_REGISTRY_NAME_OPT-token\"; echo ignored",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(token, "internal-token");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plugin_providers_speak_the_credential_protocol() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempdir().unwrap();
+        let script = temp.path().join("provider");
+        fs::write(
+            &script,
+            r#"#!/bin/sh
+echo '{"v":[1]}'
+read request
+case "$request" in
+  *'"name":"internal"'*'"kind":"get"'*) echo '{"Ok":{"kind":"get","token":"plugin-secret","cache":"session","operation_independent":true}}' ;;
+  *) echo '{"Err":{"kind":"not-found"}}' ;;
+esac
+"#,
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let provider = vec![script.to_string_lossy().into_owned()];
+        let registry = registry(vec![provider, args(&["cargo:token-from-stdout", "false"])]);
+        assert_eq!(registry_token(&registry).unwrap(), "plugin-secret");
+    }
+}
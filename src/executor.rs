@@ -1,9 +1,32 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use std::ffi::OsString;
+use std::fmt;
+use std::io;
 use std::path::Path;
-use std::process::{Command, ExitStatus};
+use std::process::{Command, ExitStatus, exit};
 
+/// Run the binary at `binary_path` with `args` as if it had been invoked directly.
+///
+/// On Unix cargox replaces itself with the binary, so it receives signals, job control and
+/// the terminal exactly as it would without cargox, and its exit status is the one the
+/// caller sees. This only returns if the binary could not be executed.
+#[cfg(unix)]
 pub fn execute_binary(binary_path: &Path, args: &[OsString]) -> Result<ExitStatus> {
+    use std::os::unix::process::CommandExt;
+
+    let err = Command::new(binary_path).args(args).exec();
+    Err(err).with_context(|| format!("failed to execute {}", binary_path.display()))
+}
+
+/// Run the binary at `binary_path` with `args` and wait for it to exit.
+///
+/// Windows cannot replace a running process, so cargox waits on a child instead. Ctrl-C
+/// is delivered to every process attached to the console, so the child receives it
+/// directly; cargox ignores it so that it outlives the child and reports its exit status.
+#[cfg(not(unix))]
+pub fn execute_binary(binary_path: &Path, args: &[OsString]) -> Result<ExitStatus> {
+    ignore_ctrl_c();
+
     let mut cmd = Command::new(binary_path);
     cmd.args(args);
 
@@ -13,3 +36,171 @@ pub fn execute_binary(binary_path: &Path, args: &[OsString]) -> Result<ExitStatu
 
     Ok(status)
 }
+
+/// Handle Ctrl-C and Ctrl-Break by doing nothing. A handler is registered rather than
+/// ignoring the events outright because ignoring them is inherited by child processes.
+#[cfg(windows)]
+fn ignore_ctrl_c() {
+    use windows_sys::Win32::System::Console::SetConsoleCtrlHandler;
+    use windows_sys::core::BOOL;
+
+    unsafe extern "system" fn handler(_ctrl_type: u32) -> BOOL {
+        1
+    }
+
+    // SAFETY: `handler` is a valid handler routine for the life of the process.
+    unsafe {
+        SetConsoleCtrlHandler(Some(handler), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn ignore_ctrl_c() {}
+
+/// Run `cmd` to completion. SIGINT, SIGTERM and SIGHUP sent to cargox while it waits are
+/// forwarded to the child, so that stopping cargox (for example on a CI timeout) stops the
+/// installer it is running instead of leaving it orphaned.
+pub fn run_to_completion(cmd: &mut Command) -> io::Result<ExitStatus> {
+    #[cfg(unix)]
+    forwarding::install();
+
+    let mut child = cmd.spawn()?;
+    #[cfg(unix)]
+    forwarding::set_child(Some(child.id()));
+    let status = child.wait();
+    #[cfg(unix)]
+    forwarding::set_child(None);
+    status
+}
+
+/// A child process that was killed by a signal. `main` exits by the same signal rather
+/// than reporting an error, since the signal was usually meant for cargox as well.
+#[derive(Debug)]
+pub struct KilledBySignal {
+    pub program: String,
+    pub status: ExitStatus,
+}
+
+impl fmt::Display for KilledBySignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match termination_signal(self.status) {
+            Some(signal) => write!(f, "{} was terminated by signal {signal}", self.program),
+            None => write!(f, "{} was terminated by a signal", self.program),
+        }
+    }
+}
+
+impl std::error::Error for KilledBySignal {}
+
+/// The error for `program` exiting unsuccessfully with `status`.
+pub fn child_failure(program: &str, status: ExitStatus) -> anyhow::Error {
+    match status.code() {
+        Some(code) => anyhow!("{program} exited with status code {code}"),
+        None => anyhow::Error::new(KilledBySignal {
+            program: program.to_owned(),
+            status,
+        }),
+    }
+}
+
+/// The signal that killed the process that exited with `status`, if any.
+pub fn termination_signal(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// Exit the way a child that finished with `status` did: with its exit code, or, if it was
+/// killed by a signal, by raising the same signal so that shells and CI see the real cause.
+/// Should the signal not end the process, cargox exits with `128 + signal` as shells do.
+pub fn exit_with_status(status: ExitStatus) -> ! {
+    if let Some(code) = status.code() {
+        exit(code);
+    }
+
+    match termination_signal(status) {
+        Some(signal) => {
+            #[cfg(unix)]
+            // SAFETY: restoring the default disposition and raising a signal have no memory
+            // safety requirements.
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+            exit(128 + signal)
+        }
+        None => exit(1),
+    }
+}
+
+#[cfg(unix)]
+mod forwarding {
+    use std::sync::Once;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    const FORWARDED: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// The process signals are forwarded to, or 0 when cargox is not waiting on one.
+    static CHILD: AtomicI32 = AtomicI32::new(0);
+
+    pub fn install() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            for signal in FORWARDED {
+                // SAFETY: `forward` only calls async-signal-safe functions.
+                unsafe {
+                    libc::signal(signal, forward as *const () as libc::sighandler_t);
+                }
+            }
+        });
+    }
+
+    pub fn set_child(pid: Option<u32>) {
+        let pid = pid.and_then(|pid| i32::try_from(pid).ok()).unwrap_or(0);
+        CHILD.store(pid, Ordering::SeqCst);
+    }
+
+    /// Pass the signal on to the child, or, with no child running, take the default
+    /// action as if no handler had been installed.
+    extern "C" fn forward(signal: libc::c_int) {
+        let pid = CHILD.load(Ordering::SeqCst);
+        // SAFETY: `kill`, `signal` and `raise` are async-signal-safe.
+        unsafe {
+            if pid > 0 {
+                libc::kill(pid, signal);
+            } else {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn child_failure_distinguishes_signals_from_exit_codes() {
+        let status = run_to_completion(Command::new("sh").args(["-c", "exit 3"])).unwrap();
+        let err = child_failure("cargo install", status);
+        assert_eq!(err.to_string(), "cargo install exited with status code 3");
+        assert!(err.downcast_ref::<KilledBySignal>().is_none());
+
+        let status = run_to_completion(Command::new("sh").args(["-c", "kill -TERM $$"])).unwrap();
+        let err = child_failure("cargo install", status).context("failed to install");
+        let killed = err.downcast_ref::<KilledBySignal>().unwrap();
+        assert_eq!(termination_signal(killed.status), Some(libc::SIGTERM));
+        assert_eq!(
+            killed.to_string(),
+            format!("cargo install was terminated by signal {}", libc::SIGTERM)
+        );
+    }
+}
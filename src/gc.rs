@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::list::format_size;
+use crate::manifest::{ProjectManifest, find_manifest};
+use crate::paths::get_install_dir;
+use crate::receipt::{Receipt, all_receipts};
+use crate::uninstall::remove_installed;
+use crate::usage::last_used;
+use crate::versions::{InstalledBinary, InstalledVersion, list_all_installed};
+
+/// Which installed versions `cargox gc` is allowed to remove.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    /// Keep the newest N versions of each binary.
+    pub keep: Option<usize>,
+    /// Remove versions that have not been executed within this many days.
+    pub unused_days: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Without any explicit policy only the newest version of each binary is kept.
+    fn effective(&self) -> Self {
+        if self.keep.is_none() && self.unused_days.is_none() {
+            Self {
+                keep: Some(1),
+                unused_days: None,
+            }
+        } else {
+            self.clone()
+        }
+    }
+}
+
+pub fn collect_garbage(policy: &RetentionPolicy, dry_run: bool) -> Result<()> {
+    let install_dir = get_install_dir()?;
+    let installed = list_all_installed()?;
+
+    let cwd = env::current_dir().context("failed to determine current directory")?;
+    let manifest = find_manifest(&cwd)?;
+    if let Some(manifest) = &manifest {
+        eprintln!("Keeping versions pinned by {}", manifest.path.display());
+    }
+    let receipts = all_receipts(&install_dir)?;
+
+    let removals = plan_removals(
+        installed,
+        &policy.effective(),
+        SystemTime::now(),
+        |entry| last_used(&install_dir, entry),
+        |entry| {
+            receipts
+                .iter()
+                .find(|receipt| receipt.describes(entry))
+                .map(|receipt| UNIX_EPOCH + Duration::from_secs(receipt.installed_at))
+        },
+        |entry| {
+            manifest
+                .as_ref()
+                .is_some_and(|manifest| is_pinned(manifest, &receipts, entry))
+        },
+    );
+
+    let stale = stale_staging_roots(&install_dir, SystemTime::now())?;
+    if removals.is_empty() && stale.is_empty() {
+        eprintln!("Nothing to remove");
+        return Ok(());
+    }
+
+    let mut freed = 0;
+    for entry in &removals {
+        let size = fs::metadata(&entry.path).map(|m| m.len()).unwrap_or(0);
+        if dry_run {
+            eprintln!(
+                "Would remove {} {} ({})",
+                entry.binary,
+                entry.version_label(),
+                format_size(size)
+            );
+        } else {
+            remove_installed(&install_dir, entry)?;
+            eprintln!(
+                "Removed {} {} ({})",
+                entry.binary,
+                entry.version_label(),
+                format_size(size)
+            );
+        }
+        freed += size;
+    }
+
+    for dir in &stale {
+        if dry_run {
+            eprintln!("Would remove stale staging directory {}", dir.display());
+        } else {
+            fs::remove_dir_all(dir)
+                .with_context(|| format!("failed to remove {}", dir.display()))?;
+            eprintln!("Removed stale staging directory {}", dir.display());
+        }
+    }
+
+    if dry_run {
+        eprintln!("Would free {}", format_size(freed));
+    } else {
+        eprintln!("Freed {}", format_size(freed));
+    }
+
+    Ok(())
+}
+
+/// Staging roots are removed when their install finishes, so one this old was left behind
+/// by an install that was killed.
+const STALE_STAGING_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The staging roots under `staging/` that were created more than [`STALE_STAGING_AGE`]
+/// before `now`.
+fn stale_staging_roots(install_dir: &Path, now: SystemTime) -> Result<Vec<PathBuf>> {
+    let staging_dir = install_dir.join("staging");
+    let entries = match fs::read_dir(&staging_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).context(format!("failed to read {}", staging_dir.display())),
+    };
+
+    let cutoff = now - STALE_STAGING_AGE;
+    let mut stale = vec![];
+    for entry in entries {
+        let entry = entry.context("failed to iterate staging roots")?;
+        if !entry.file_name().to_string_lossy().starts_with("install-") {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() && metadata.modified().is_ok_and(|modified| modified < cutoff) {
+            stale.push(entry.path());
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+/// Whether the project manifest pins a requirement that `entry` satisfies, in the registry it
+/// was installed from.
+fn is_pinned(manifest: &ProjectManifest, receipts: &[Receipt], entry: &InstalledBinary) -> bool {
+    let crate_name = receipts
+        .iter()
+        .find(|receipt| receipt.describes(entry))
+        .map(|receipt| receipt.crate_name.as_str())
+        .unwrap_or(&entry.binary);
+
+    manifest.tool(crate_name).is_some_and(|tool| {
+        tool.registry == entry.registry && entry.version.matches(&tool.requirement)
+    })
+}
+
+/// Releases, git builds and path builds of a binary are each kept separately, as are the
+/// releases of each registry: releases are ranked by version and builds, which have no
+/// meaningful order of their own, by when they were installed.
+fn plan_removals(
+    installed: Vec<InstalledBinary>,
+    policy: &RetentionPolicy,
+    now: SystemTime,
+    last_used: impl Fn(&InstalledBinary) -> Option<SystemTime>,
+    installed_at: impl Fn(&InstalledBinary) -> Option<SystemTime>,
+    pinned: impl Fn(&InstalledBinary) -> bool,
+) -> Vec<InstalledBinary> {
+    let mut groups: BTreeMap<(String, Option<String>), Vec<InstalledBinary>> = BTreeMap::new();
+    for entry in installed {
+        groups
+            .entry((entry.binary.clone(), entry.registry.clone()))
+            .or_default()
+            .push(entry);
+    }
+
+    let cutoff = policy
+        .unused_days
+        .map(|days| now - Duration::from_secs(days * 24 * 60 * 60));
+
+    let mut removals = Vec::new();
+    for (_, versions) in groups {
+        let (mut releases, builds): (Vec<_>, Vec<_>) = versions
+            .into_iter()
+            .partition(|entry| entry.version.release().is_some());
+        let (mut git, mut path): (Vec<_>, Vec<_>) = builds
+            .into_iter()
+            .partition(|entry| matches!(entry.version, InstalledVersion::Git(_)));
+
+        releases.sort_by(|a, b| b.version.cmp(&a.version));
+        git.sort_by_key(|entry| Reverse(installed_at(entry)));
+        path.sort_by_key(|entry| Reverse(installed_at(entry)));
+
+        for ranked in [releases, git, path] {
+            for (idx, entry) in ranked.into_iter().enumerate() {
+                let beyond_keep = policy.keep.is_some_and(|keep| idx >= keep);
+                let unused =
+                    cutoff.is_some_and(|cutoff| last_used(&entry).is_none_or(|used| used < cutoff));
+                if (beyond_keep || unused) && !pinned(&entry) {
+                    removals.push(entry);
+                }
+            }
+        }
+    }
+
+    removals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::test_receipt;
+    use crate::versions::test_installed;
+
+    fn names(entries: &[InstalledBinary]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| format!("{}-{}", entry.binary, entry.version))
+            .collect()
+    }
+
+    #[test]
+    fn default_policy_keeps_newest_version() {
+        let all = vec![
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.1.0"),
+            test_installed("bat", "0.24.0"),
+        ];
+        let policy = RetentionPolicy::default().effective();
+        let removals = plan_removals(
+            all,
+            &policy,
+            SystemTime::now(),
+            |_| None,
+            |_| None,
+            |_| false,
+        );
+        assert_eq!(names(&removals), vec!["rg-13.0.0"]);
+    }
+
+    #[test]
+    fn registries_are_kept_separately() {
+        let internal = |version| InstalledBinary {
+            registry: Some("internal".into()),
+            ..test_installed("rg", version)
+        };
+        let all = vec![
+            test_installed("rg", "14.1.0"),
+            internal("13.0.0"),
+            internal("14.1.0"),
+        ];
+        let removals = plan_removals(
+            all,
+            &RetentionPolicy::default().effective(),
+            SystemTime::now(),
+            |_| None,
+            |_| None,
+            |_| false,
+        );
+        assert_eq!(removals.len(), 1);
+        assert_eq!(removals[0].version_label(), "13.0.0 (internal)");
+    }
+
+    #[test]
+    fn keep_policy_retains_newest_n() {
+        let all = vec![
+            test_installed("rg", "12.0.0"),
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.1.0"),
+        ];
+        let policy = RetentionPolicy {
+            keep: Some(2),
+            unused_days: None,
+        };
+        let removals = plan_removals(
+            all,
+            &policy,
+            SystemTime::now(),
+            |_| None,
+            |_| None,
+            |_| false,
+        );
+        assert_eq!(names(&removals), vec!["rg-12.0.0"]);
+    }
+
+    #[test]
+    fn builds_are_kept_separately_from_releases() {
+        let now = SystemTime::now();
+        let all = vec![
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.1.0"),
+            test_installed("rg", "git.0123456789ab"),
+            test_installed("rg", "git.ba9876543210"),
+        ];
+        let policy = RetentionPolicy::default().effective();
+        let removals = plan_removals(
+            all,
+            &policy,
+            now,
+            |_| None,
+            |entry| {
+                if entry.path.ends_with("rg-git.ba9876543210") {
+                    Some(now)
+                } else {
+                    Some(now - Duration::from_secs(60))
+                }
+            },
+            |_| false,
+        );
+        assert_eq!(names(&removals), vec!["rg-13.0.0", "rg-git.0123456789ab"]);
+    }
+
+    #[test]
+    fn unused_policy_removes_stale_versions() {
+        let now = SystemTime::now();
+        let all = vec![
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.1.0"),
+        ];
+        let policy = RetentionPolicy {
+            keep: None,
+            unused_days: Some(30),
+        };
+        let removals = plan_removals(
+            all,
+            &policy,
+            now,
+            |entry| {
+                if entry.path.ends_with("rg-13.0.0") {
+                    Some(now - Duration::from_secs(60 * 24 * 60 * 60))
+                } else {
+                    Some(now)
+                }
+            },
+            |_| None,
+            |_| false,
+        );
+        assert_eq!(names(&removals), vec!["rg-13.0.0"]);
+    }
+
+    #[test]
+    fn pinned_versions_are_kept() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(
+            temp.path().join("cargox.toml"),
+            "[tools]
+ripgrep = \"13\"\n",
+        )
+        .unwrap();
+        let manifest = find_manifest(temp.path()).unwrap().unwrap();
+        let receipts = vec![test_receipt("ripgrep", "rg", "13.0.0")];
+
+        let all = vec![
+            test_installed("rg", "12.0.0"),
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.1.0"),
+        ];
+        let removals = plan_removals(
+            all,
+            &RetentionPolicy::default().effective(),
+            SystemTime::now(),
+            |_| None,
+            |_| None,
+            |entry| is_pinned(&manifest, &receipts, entry),
+        );
+        assert_eq!(names(&removals), vec!["rg-12.0.0"]);
+    }
+
+    #[test]
+    fn stale_staging_roots_are_found_by_age() {
+        let temp = tempfile::tempdir().unwrap();
+        let staging = temp.path().join("staging");
+        fs::create_dir_all(staging.join("install-abc")).unwrap();
+        fs::create_dir_all(staging.join("other")).unwrap();
+
+        let now = SystemTime::now();
+        assert!(stale_staging_roots(temp.path(), now).unwrap().is_empty());
+        assert_eq!(
+            stale_staging_roots(temp.path(), now + STALE_STAGING_AGE * 2).unwrap(),
+            vec![staging.join("install-abc")]
+        );
+        assert!(
+            stale_staging_roots(&temp.path().join("missing"), now)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
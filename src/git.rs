@@ -0,0 +1,332 @@
+use anyhow::{Context, Result, anyhow};
+use std::fmt;
+use std::process::Command;
+
+use crate::receipt::Receipt;
+use crate::versions::InstalledVersion;
+
+/// A crate installed straight from a git repository rather than a registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSource {
+    pub url: String,
+    /// The branch, tag or commit to build; the remote's `HEAD` when `None`.
+    pub rev: Option<String>,
+    /// The package to install, for repositories with more than one; when `None` cargo
+    /// picks the repository's only binary package.
+    pub package: Option<String>,
+}
+
+impl fmt::Display for GitSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.url)?;
+        if let Some(rev) = &self.rev {
+            write!(f, "@{rev}")?;
+        }
+        if let Some(package) = &self.package {
+            write!(f, "#{package}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a `git+<url>[@<rev>][#<package>]` spec into the crate name and the source. Returns
+/// `None` for specs that do not start with `git+`. Without a `#<package>` the crate name is
+/// guessed from the last path segment of the URL.
+///
+/// The revision starts at the first `@` in the URL's path, so that
+/// `git+ssh://git@host/org/tool` keeps its user name and branches such as `release/1.0`
+/// keep their slashes.
+pub fn parse_git_spec(spec: &str) -> Result<Option<(String, GitSource)>> {
+    let Some(url) = spec.trim().strip_prefix("git+") else {
+        return Ok(None);
+    };
+
+    let (url, package) = match url.rsplit_once('#') {
+        Some((_, "")) => {
+            return Err(anyhow!(
+                "invalid crate spec `{spec}`: package cannot be empty after `#`"
+            ));
+        }
+        Some((url, package)) => (url, Some(package.to_owned())),
+        None => (url, None),
+    };
+
+    let path_start = path_start(url);
+    let (url, rev) = match url[path_start..].find('@') {
+        Some(at) => {
+            let (url, rev) = (&url[..path_start + at], &url[path_start + at + 1..]);
+            if rev.is_empty() {
+                return Err(anyhow!(
+                    "invalid crate spec `{spec}`: revision cannot be empty after `@`"
+                ));
+            }
+            (url, Some(rev.to_owned()))
+        }
+        None => (url, None),
+    };
+
+    let guessed = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .map(|segment| segment.trim_end_matches(".git"))
+        .filter(|segment| !segment.is_empty())
+        .ok_or_else(|| anyhow!("invalid crate spec `{spec}`: expected a repository URL"))?;
+    let name = package.clone().unwrap_or_else(|| guessed.to_owned());
+
+    Ok(Some((
+        name,
+        GitSource {
+            url: url.to_owned(),
+            rev,
+            package,
+        },
+    )))
+}
+
+/// Where the path of a repository URL starts: after the host of `scheme://host/path`, or
+/// after the `:` of an scp-like `user@host:path`.
+fn path_start(url: &str) -> usize {
+    match url.find("://") {
+        Some(scheme_end) => {
+            let host_start = scheme_end + "://".len();
+            url[host_start..]
+                .find('/')
+                .map_or(url.len(), |slash| host_start + slash)
+        }
+        None => url.find(':').map_or(0, |colon| colon + 1),
+    }
+}
+
+/// Whether a package source recorded by cargo, such as
+/// `git+https://github.com/org/tool?rev=0123abc#0123abcdef…`, is a checkout of `url`.
+pub fn is_checkout_of(source: &str, url: &str) -> bool {
+    source
+        .strip_prefix("git+")
+        .and_then(|rest| rest.strip_prefix(url))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['?', '#']))
+}
+
+/// The binary an earlier build of `version` from `source` installed, found by the package
+/// name cargo resolved rather than the one guessed from the URL.
+pub fn built_binary(
+    receipts: &[Receipt],
+    source: &GitSource,
+    version: &InstalledVersion,
+    bin: Option<&str>,
+) -> Option<String> {
+    receipts
+        .iter()
+        .filter(|receipt| {
+            &receipt.version == version
+                && receipt
+                    .source
+                    .as_deref()
+                    .is_some_and(|recorded| is_checkout_of(recorded, &source.url))
+                && source
+                    .package
+                    .as_ref()
+                    .is_none_or(|package| *package == receipt.crate_name)
+        })
+        .find(|receipt| match bin {
+            Some(bin) => receipt.binary == bin,
+            None => receipt.binaries.len() == 1 || receipt.binary == receipt.crate_name,
+        })
+        .map(|receipt| receipt.binary.clone())
+}
+
+/// Whether `rev` is a full commit hash, which needs no lookup to identify a build.
+pub fn is_commit_hash(rev: &str) -> bool {
+    matches!(rev.len(), 40 | 64) && rev.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// The commit `source` currently points at, asking the remote with `git ls-remote` unless
+/// the revision already is a full commit hash.
+pub fn resolve_commit(source: &GitSource) -> Result<String> {
+    if let Some(rev) = source.rev.as_deref().filter(|rev| is_commit_hash(rev)) {
+        return Ok(rev.to_ascii_lowercase());
+    }
+
+    let pattern = source.rev.as_deref().unwrap_or("HEAD");
+    let output = Command::new("git")
+        .args(["ls-remote", "--", &source.url, pattern])
+        .output()
+        .context("failed to run `git ls-remote`; is git installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git ls-remote {} failed: {}",
+            source.url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let listing = String::from_utf8_lossy(&output.stdout);
+    find_ref(&listing, pattern).ok_or_else(|| {
+        anyhow!(
+            "`{pattern}` is not a branch or tag of {}; abbreviated commit hashes cannot be resolved remotely, so pass the full hash",
+            source.url
+        )
+    })
+}
+
+/// Pick the commit for `rev` from `git ls-remote` output, resolving names in the order
+/// git itself does: an exact ref, then a tag (peeled to its commit), then a branch.
+fn find_ref(listing: &str, rev: &str) -> Option<String> {
+    let refs: Vec<(&str, &str)> = listing
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect();
+
+    [
+        rev.to_owned(),
+        format!("refs/tags/{rev}^{{}}"),
+        format!("refs/tags/{rev}"),
+        format!("refs/heads/{rev}"),
+    ]
+    .iter()
+    .find_map(|wanted| {
+        refs.iter()
+            .find(|(_, name)| name == wanted)
+            .map(|(commit, _)| commit.to_ascii_lowercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::test_receipt;
+
+    const COMMIT: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn parse_git_spec_splits_revision() {
+        let (name, source) = parse_git_spec("git+https://github.com/org/tool@v1.2.0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tool");
+        assert_eq!(source.url, "https://github.com/org/tool");
+        assert_eq!(source.rev.as_deref(), Some("v1.2.0"));
+
+        let (name, source) = parse_git_spec("git+ssh://git@github.com/org/tool.git")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tool");
+        assert_eq!(source.url, "ssh://git@github.com/org/tool.git");
+        assert_eq!(source.rev, None);
+
+        let (name, source) = parse_git_spec("git+https://github.com/org/tools@main#tool-cli")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tool-cli");
+        assert_eq!(source.url, "https://github.com/org/tools");
+        assert_eq!(source.rev.as_deref(), Some("main"));
+        assert_eq!(source.package.as_deref(), Some("tool-cli"));
+        assert_eq!(
+            source.to_string(),
+            "https://github.com/org/tools@main#tool-cli"
+        );
+
+        let (name, source) = parse_git_spec("git+https://github.com/org/tool@release/1.0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tool");
+        assert_eq!(source.url, "https://github.com/org/tool");
+        assert_eq!(source.rev.as_deref(), Some("release/1.0"));
+
+        let (name, source) = parse_git_spec("git+git@github.com:org/tool.git@feature/x#tool-cli")
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tool-cli");
+        assert_eq!(source.url, "git@github.com:org/tool.git");
+        assert_eq!(source.rev.as_deref(), Some("feature/x"));
+
+        assert!(parse_git_spec("ripgrep@14").unwrap().is_none());
+        assert!(parse_git_spec("git+https://github.com/org/tools#").is_err());
+        assert!(parse_git_spec("git+https://github.com/org/tool@").is_err());
+        assert!(parse_git_spec("git+https://").is_err());
+    }
+
+    #[test]
+    fn is_checkout_of_matches_whole_url() {
+        let source = format!("git+https://github.com/org/tool?rev=main#{COMMIT}");
+        assert!(is_checkout_of(&source, "https://github.com/org/tool"));
+        assert!(!is_checkout_of(&source, "https://github.com/org/to"));
+        assert!(!is_checkout_of(
+            "registry+https://github.com/rust-lang/crates.io-index",
+            "https://github.com/org/tool"
+        ));
+    }
+
+    #[test]
+    fn built_binary_uses_recorded_package() {
+        let source = GitSource {
+            url: "https://github.com/org/tools".into(),
+            rev: None,
+            package: None,
+        };
+        let version = InstalledVersion::git(COMMIT);
+        let receipt = Receipt {
+            installer: crate::receipt::Installer::CargoInstall,
+            source: Some(format!(
+                "git+https://github.com/org/tools?rev={COMMIT}#{COMMIT}"
+            )),
+            ..test_receipt("tool-cli", "tool", &version.to_string())
+        };
+        let receipts = [receipt];
+
+        assert_eq!(
+            built_binary(&receipts, &source, &version, None).as_deref(),
+            Some("tool")
+        );
+        assert_eq!(
+            built_binary(&receipts, &source, &version, Some("other")),
+            None
+        );
+        assert_eq!(
+            built_binary(&receipts, &source, &InstalledVersion::git("ab"), None),
+            None
+        );
+
+        let other_package = GitSource {
+            package: Some("tool-server".into()),
+            ..source
+        };
+        assert_eq!(
+            built_binary(&receipts, &other_package, &version, None),
+            None
+        );
+    }
+
+    #[test]
+    fn is_commit_hash_requires_full_hash() {
+        assert!(is_commit_hash(COMMIT));
+        assert!(!is_commit_hash("0123456"));
+        assert!(!is_commit_hash("main"));
+    }
+
+    #[test]
+    fn find_ref_prefers_peeled_tags_then_branches() {
+        let listing = format!(
+            "1111111111111111111111111111111111111111\trefs/heads/v1\n\
+             2222222222222222222222222222222222222222\trefs/tags/v1\n\
+             {COMMIT}\trefs/tags/v1^{{}}\n\
+             3333333333333333333333333333333333333333\trefs/heads/main\n\
+             4444444444444444444444444444444444444444\trefs/heads/feature/main\n"
+        );
+
+        assert_eq!(find_ref(&listing, "v1").as_deref(), Some(COMMIT));
+        assert_eq!(
+            find_ref(&listing, "main").as_deref(),
+            Some("3333333333333333333333333333333333333333")
+        );
+        assert_eq!(
+            find_ref(&listing, "feature/main").as_deref(),
+            Some("4444444444444444444444444444444444444444")
+        );
+        assert_eq!(find_ref(&listing, "missing"), None);
+        assert_eq!(
+            find_ref(&format!("{COMMIT}\tHEAD\n"), "HEAD").as_deref(),
+            Some(COMMIT)
+        );
+    }
+}
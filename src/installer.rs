@@ -1,21 +1,96 @@
 use anyhow::{Context, Result, anyhow};
 use semver::Version;
+use std::ffi::OsString;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::TempDir;
 
-use crate::cli::Cli;
-use crate::paths::get_install_dir;
+use crate::checksum::verify_downloaded_crate;
+use crate::cli::{Cli, Verification};
+use crate::executor::{KilledBySignal, child_failure, run_to_completion};
+use crate::git::GitSource;
+use crate::local::LocalSource;
+use crate::lock::{lock_install, lock_install_root};
+use crate::paths::{cargo_home, get_install_dir};
+use crate::receipt::{Installer, Receipt, write_receipt};
+use crate::registry::{fetch_checksum, index_url};
 use crate::target::Target;
-use crate::versions::versioned_binary_path;
+use crate::tracking::{TrackedInstall, tracked_installs};
+use crate::verify::{check_executable, check_reported_version};
+use crate::versions::{InstalledVersion, find_exact_version, versioned_binary_path};
 
-pub fn ensure_installed(target: &Target, cli: &Cli, version: &Version) -> Result<()> {
-    if !cli.build_from_source && which::which("cargo-binstall").is_ok() {
-        install_with_binstall(target, cli, version)
-    } else {
-        log_fallback_reason(cli, target, version);
-        install_with_cargo(target, cli, version)
+/// Install `target` at `version` and return the path of the versioned binary to run.
+/// `pinned` is the checksum `cargox.lock` records for the version, which the registry must
+/// still list. When the registry's checksum cannot be looked up the install goes ahead,
+/// checked against the pinned checksum if there is one.
+pub fn ensure_installed(
+    target: &Target,
+    cli: &Cli,
+    version: &Version,
+    pinned: Option<&str>,
+) -> Result<PathBuf> {
+    install_locked(target, cli, &version.clone().into(), || {
+        let checksum = match fetch_checksum(&target.crate_name, version, target.registry()) {
+            Ok(checksum) => {
+                if let Some(pinned) = pinned
+                    && !pinned.eq_ignore_ascii_case(&checksum)
+                {
+                    return Err(anyhow!(
+                        "the registry lists checksum {checksum} for {}@{version}, but cargox.lock records {pinned}",
+                        target.crate_name
+                    ));
+                }
+                Some(checksum)
+            }
+            Err(err) => {
+                eprintln!(
+                    "warning: could not look up the checksum of {}@{version}: {err:#}",
+                    target.crate_name
+                );
+                pinned.map(str::to_owned)
+            }
+        };
+
+        if !cli.build_from_source && which::which("cargo-binstall").is_ok() {
+            // cargo-binstall is not allowed to build from source itself, since its builds
+            // would skip the checksum check; cargox builds the crate instead.
+            match install_with_binstall(target, cli, version, checksum.as_deref()) {
+                Err(err) if err.downcast_ref::<KilledBySignal>().is_none() => {
+                    eprintln!(
+                        "cargo-binstall could not install a prebuilt {}@{version} ({err:#}); building it from source with cargo install",
+                        target.crate_name
+                    );
+                    install_with_cargo(target, cli, version, checksum.as_deref())
+                }
+                result => result,
+            }
+        } else {
+            log_fallback_reason(cli, target, version);
+            install_with_cargo(target, cli, version, checksum.as_deref())
+        }
+    })
+}
+
+/// Run `install` while holding the lock for `version` of the target. A concurrent run may
+/// have installed it while this one waited for the lock, in which case its binary is used.
+fn install_locked(
+    target: &Target,
+    cli: &Cli,
+    version: &InstalledVersion,
+    install: impl FnOnce() -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    let install_dir = get_install_dir()?;
+    let registry = target.registry_name();
+    let lock = lock_install(&install_dir, &target.crate_name, version, registry)?;
+    // `--force` reinstalls, unless the install it waited for has just done so.
+    if (!cli.force || lock.waited())
+        && let Some(installed) = find_exact_version(&target.binary, registry, version)?
+    {
+        return Ok(installed.path);
     }
+    install()
 }
 
 fn log_fallback_reason(cli: &Cli, target: &Target, version: &Version) {
@@ -32,9 +107,16 @@ fn log_fallback_reason(cli: &Cli, target: &Target, version: &Version) {
     }
 }
 
-fn install_with_binstall(target: &Target, cli: &Cli, version: &Version) -> Result<()> {
+/// Install a prebuilt binary. The binary is not built from the `.crate` file, so its
+/// checksum is only recorded. Fails when no prebuilt binary is found.
+fn install_with_binstall(
+    target: &Target,
+    cli: &Cli,
+    version: &Version,
+    checksum: Option<&str>,
+) -> Result<PathBuf> {
     let install_dir = get_install_dir()?;
-    ensure_bin_dir(&install_dir)?;
+    let staging = staging_root(&install_dir)?;
 
     let mut cmd = Command::new("cargo");
     cmd.arg("binstall");
@@ -43,15 +125,15 @@ fn install_with_binstall(target: &Target, cli: &Cli, version: &Version) -> Resul
     }
     cmd.arg("--no-confirm");
     cmd.arg("--force");
-    if let Some(bin) = &cli.bin {
-        cmd.arg("--bin");
-        cmd.arg(bin);
+    cmd.args(["--disable-strategies", "compile"]);
+    if let Some(registry) = target.registry() {
+        cmd.args(registry.install_args());
     }
     cmd.arg(format!("{}@{}", target.crate_name, version));
 
-    // Set the install root for cargo-binstall and remove any environment variables
-    // that could leak into the installation process
-    sanitize_cargo_env(&mut cmd, &install_dir);
+    // Install into the staging root and remove any environment variables that could leak
+    // into the installation process
+    sanitize_cargo_env(&mut cmd, staging.path());
 
     eprintln!(
         "Installing {}@{} with cargo-binstall{} to {}",
@@ -61,23 +143,93 @@ fn install_with_binstall(target: &Target, cli: &Cli, version: &Version) -> Resul
         install_dir.display()
     );
 
-    let status = cmd.status().context("failed to invoke cargo-binstall")?;
+    let status = run_to_completion(&mut cmd).context("failed to invoke cargo-binstall")?;
     if status.success() {
-        finalize_installation(&install_dir, &target.binary, version)
+        finalize_installation(
+            &install_dir,
+            staging.path(),
+            target,
+            cli,
+            &version.clone().into(),
+            Installer::CargoBinstall,
+            checksum,
+        )
     } else {
-        Err(anyhow!(
-            "cargo-binstall exited with status code {}",
-            status
-                .code()
-                .map(|c| c.to_string())
-                .unwrap_or_else(|| "signal".to_string())
-        ))
+        Err(child_failure("cargo-binstall", status))
     }
 }
 
-fn install_with_cargo(target: &Target, cli: &Cli, version: &Version) -> Result<()> {
+fn install_with_cargo(
+    target: &Target,
+    cli: &Cli,
+    version: &Version,
+    checksum: Option<&str>,
+) -> Result<PathBuf> {
+    let mut source_args: Vec<OsString> = vec![];
+    if let Some(registry) = target.registry() {
+        source_args.extend(registry.install_args().map(OsString::from));
+    }
+    source_args.push(target.crate_name.clone().into());
+    source_args.push("--version".into());
+    source_args.push(version.to_string().into());
+
+    run_cargo_install(target, cli, &version.clone().into(), &source_args, checksum)
+}
+
+/// Build `commit` of the repository `source` with `cargo install --git` and return the path
+/// of the binary to run, versioned by the commit.
+pub fn install_git_commit(
+    target: &Target,
+    cli: &Cli,
+    source: &GitSource,
+    commit: &str,
+) -> Result<PathBuf> {
+    let mut source_args: Vec<OsString> = vec![
+        "--git".into(),
+        source.url.clone().into(),
+        "--rev".into(),
+        commit.into(),
+    ];
+    if let Some(package) = &source.package {
+        source_args.push(package.into());
+    }
+    let version = InstalledVersion::git(commit);
+    install_locked(target, cli, &version, || {
+        run_cargo_install(target, cli, &version, &source_args, None)
+    })
+}
+
+/// Build the package in `source` with `cargo install --path` and return the path of the
+/// binary to run, versioned by the fingerprint of its sources.
+pub fn install_local(
+    target: &Target,
+    cli: &Cli,
+    source: &LocalSource,
+    version: &InstalledVersion,
+) -> Result<PathBuf> {
+    let source_args: Vec<OsString> = vec!["--path".into(), source.dir.clone().into()];
+    install_locked(target, cli, version, || {
+        run_cargo_install(target, cli, version, &source_args, None)
+    })
+}
+
+/// Build with `cargo install` into a staging root. `checksum` is the registry's checksum of
+/// the release being built, which the `.crate` file cargo built from must match.
+fn run_cargo_install(
+    target: &Target,
+    cli: &Cli,
+    version: &InstalledVersion,
+    source_args: &[OsString],
+    checksum: Option<&str>,
+) -> Result<PathBuf> {
     let install_dir = get_install_dir()?;
-    ensure_bin_dir(&install_dir)?;
+    let staging = staging_root(&install_dir)?;
+
+    // A copy already in Cargo's download cache is used without being checked again, so it
+    // is checked before any of its build scripts can run.
+    if let (Some(checksum), Some(release)) = (checksum, version.release()) {
+        verify_source(target, release, checksum)?;
+    }
 
     // Create a temporary directory for the build
     let temp_dir = tempfile::tempdir().context("failed to create temp directory")?;
@@ -90,19 +242,17 @@ fn install_with_cargo(target: &Target, cli: &Cli, version: &Version) -> Result<(
     if cli.force {
         cmd.arg("--force");
     }
-    cmd.arg("--root");
-    cmd.arg(&install_dir);
-    cmd.arg(&target.crate_name);
-    cmd.arg("--version");
-    cmd.arg(version.to_string());
-    if let Some(bin) = &cli.bin {
-        cmd.arg("--bin");
-        cmd.arg(bin);
+    // Only local builds get this far offline; their dependencies may already be cached.
+    if cli.offline {
+        cmd.arg("--offline");
     }
+    cmd.arg("--root");
+    cmd.arg(staging.path());
+    cmd.args(source_args);
 
     // Use temp directory for target build directory and sanitize environment
     cmd.env("CARGO_TARGET_DIR", temp_dir.path());
-    sanitize_cargo_env(&mut cmd, &install_dir);
+    sanitize_cargo_env(&mut cmd, staging.path());
 
     eprintln!(
         "Installing {}@{} with cargo install{} to {}",
@@ -112,23 +262,49 @@ fn install_with_cargo(target: &Target, cli: &Cli, version: &Version) -> Result<(
         install_dir.display()
     );
 
-    let status = cmd.status().context("failed to invoke cargo install")?;
+    let status = run_to_completion(&mut cmd).context("failed to invoke cargo install")?;
 
     // Temp directory will be automatically cleaned up when temp_dir goes out of scope
 
     if status.success() {
-        finalize_installation(&install_dir, &target.binary, version)
+        // A fresh download has been checked by cargo against its copy of the index; this
+        // confirms it matches the checksum cargox looked up.
+        if let (Some(checksum), Some(release)) = (checksum, version.release())
+            && !verify_source(target, release, checksum)?
+        {
+            eprintln!(
+                "warning: could not find the downloaded {}-{release}.crate; its checksum was not verified",
+                target.crate_name
+            );
+        }
+        finalize_installation(
+            &install_dir,
+            staging.path(),
+            target,
+            cli,
+            version,
+            Installer::CargoInstall,
+            checksum,
+        )
     } else {
-        Err(anyhow!(
-            "cargo install exited with status code {}",
-            status
-                .code()
-                .map(|c| c.to_string())
-                .unwrap_or_else(|| "signal".to_string())
-        ))
+        Err(child_failure("cargo install", status))
     }
 }
 
+/// Check the `.crate` file of `release` in Cargo's download cache against the registry's
+/// checksum. Returns whether a copy was found: there is none before the first download, and
+/// the cache cannot always be found, for example behind a source replacement.
+fn verify_source(target: &Target, release: &Version, checksum: &str) -> Result<bool> {
+    let cargo_home = cargo_home().ok_or_else(|| anyhow!("unable to determine the Cargo home"))?;
+    verify_downloaded_crate(
+        &cargo_home,
+        index_url(target.registry()),
+        &target.crate_name,
+        release,
+        checksum,
+    )
+}
+
 /// Sanitize the environment for cargo commands to ensure complete sandboxing.
 /// Removes any Cargo-related environment variables that could leak into the installation
 /// and sets only the variables we explicitly want.
@@ -152,64 +328,390 @@ fn sanitize_cargo_env(cmd: &mut Command, install_dir: &std::path::Path) {
     cmd.env("CARGO_INSTALL_ROOT", install_dir);
 }
 
-fn finalize_installation(install_dir: &Path, binary: &str, version: &Version) -> Result<()> {
-    let bin_dir = install_dir.join("bin");
-    let installed_path = {
-        let candidate = bin_dir.join(binary);
-        if candidate.exists() {
-            candidate
-        } else {
-            #[cfg(windows)]
-            {
-                let exe_candidate = bin_dir.join(binary).with_extension("exe");
-                if exe_candidate.exists() {
-                    exe_candidate
-                } else {
-                    return Err(anyhow!(
-                        "expected installer to create {}, but it was not found",
-                        candidate.display()
-                    ));
-                }
-            }
-            #[cfg(not(windows))]
-            {
-                return Err(anyhow!(
-                    "expected installer to create {}, but it was not found",
-                    candidate.display()
-                ));
-            }
-        }
+/// Move every binary the install produced from the staging root into the shared `bin`
+/// directory under its versioned name, so that a later `--bin` for another binary of the
+/// same crate version is a cache hit, then return the path of the binary to run. Nothing
+/// outside the staging root is touched until the install has been checked.
+fn finalize_installation(
+    install_dir: &Path,
+    staging: &Path,
+    target: &Target,
+    cli: &Cli,
+    version: &InstalledVersion,
+    installer: Installer,
+    checksum: Option<&str>,
+) -> Result<PathBuf> {
+    // A git repository's package name is only a guess until cargo reports it, so git
+    // builds are found by their commit alone.
+    let tracked = tracked_installs(staging)?.into_iter().find(|install| {
+        install.is_build(version)
+            && (matches!(version, InstalledVersion::Git(_))
+                || install.crate_name == target.crate_name)
+    });
+    let produced = match &tracked {
+        Some(install) if !install.bins.is_empty() => install.bins.clone(),
+        _ => vec![cli.bin.clone().unwrap_or_else(|| target.binary.clone())],
     };
 
-    let target_path = versioned_binary_path(binary, version)?;
-    if target_path.exists() {
-        fs::remove_file(&target_path).with_context(|| {
+    // Without `--bin`, a crate with several binaries is still installed before asking
+    // which one to run, so that the next run with `--bin` does not build it again.
+    let selection = select_binary(target, cli.bin.as_deref(), &produced);
+    if let Ok(binary) = &selection
+        && !produced.contains(binary)
+    {
+        return Err(anyhow!(
+            "{} does not provide a `{binary}` binary; it provides: {}",
+            target.crate_name,
+            produced.join(", ")
+        ));
+    }
+    let selected = selection
+        .as_ref()
+        .ok()
+        .and_then(|binary| produced.iter().position(|name| name == binary));
+    let staged_bin_dir = staging.join("bin");
+    let staged = produced
+        .iter()
+        .map(|binary| staged_binary(&staged_bin_dir, binary))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(verification) = cli.verify {
+        let selected = selected.map(|index| staged[index].as_path());
+        verify_staged(&staged, selected, version, verification).with_context(|| {
             format!(
-                "failed to replace existing installation {}",
-                target_path.display()
+                "{}@{version} failed verification and was not installed",
+                target.crate_name
             )
         })?;
     }
 
-    fs::rename(&installed_path, &target_path).with_context(|| {
+    let mut receipt = installation_receipt(
+        target,
+        &produced,
+        version,
+        installer,
+        tracked.as_ref(),
+        checksum,
+    );
+    let bin_dir = ensure_bin_dir(install_dir)?;
+    let _root = lock_install_root(install_dir)?;
+    let registry = target.registry_name();
+    for (name, path) in produced.iter().zip(&staged) {
+        move_into_place(&bin_dir, path, name, version, registry)?;
+        receipt.binary = name.clone();
+        write_receipt(install_dir, &receipt)?;
+    }
+
+    let binary = selection?;
+    if binary != target.binary {
+        eprintln!("{} provides the `{binary}` binary", target.crate_name);
+    }
+
+    Ok(versioned_binary_path(&bin_dir, &binary, version, registry))
+}
+
+/// Check staged binaries before they replace anything. Every binary must be an executable
+/// for this platform, and with `version` checks the one being run, if it is known, must also
+/// report the release that was installed. Git and path builds have no release to compare.
+fn verify_staged(
+    staged: &[PathBuf],
+    selected: Option<&Path>,
+    version: &InstalledVersion,
+    verification: Verification,
+) -> Result<()> {
+    for path in staged {
+        check_executable(path)?;
+    }
+    if verification == Verification::Version
+        && let Some(selected) = selected
+        && let Some(release) = version.release()
+    {
+        check_reported_version(selected, release)?;
+    }
+    Ok(())
+}
+
+/// A private install root for one installer run, inside the install root so that its
+/// binaries can be renamed into place. It is removed when dropped, so a failed install
+/// leaves nothing behind; `cargox gc` removes those of installs that were killed.
+fn staging_root(install_dir: &Path) -> Result<TempDir> {
+    let staging_dir = install_dir.join("staging");
+    fs::create_dir_all(&staging_dir)
+        .with_context(|| format!("failed to create {}", staging_dir.display()))?;
+    tempfile::Builder::new()
+        .prefix("install-")
+        .tempdir_in(&staging_dir)
+        .with_context(|| format!("failed to create a directory in {}", staging_dir.display()))
+}
+
+/// The path of `binary` in a staging root's `bin` directory.
+fn staged_binary(bin_dir: &Path, binary: &str) -> Result<PathBuf> {
+    let candidate = bin_dir.join(binary);
+    if candidate.is_file() {
+        return Ok(candidate);
+    }
+    #[cfg(windows)]
+    {
+        let exe_candidate = candidate.with_extension("exe");
+        if exe_candidate.is_file() {
+            return Ok(exe_candidate);
+        }
+    }
+    Err(anyhow!(
+        "expected installer to create {}, but it was not found",
+        candidate.display()
+    ))
+}
+
+/// Rename a staged binary to its versioned name in the shared `bin` directory, replacing
+/// any previous install of the same version in one step.
+fn move_into_place(
+    bin_dir: &Path,
+    staged: &Path,
+    binary: &str,
+    version: &InstalledVersion,
+    registry: Option<&str>,
+) -> Result<PathBuf> {
+    let target_path = versioned_binary_path(bin_dir, binary, version, registry);
+    fs::rename(staged, &target_path).with_context(|| {
         format!(
             "failed to move installed binary from {} to {}",
-            installed_path.display(),
+            staged.display(),
             target_path.display()
         )
     })?;
 
-    Ok(())
+    Ok(target_path)
+}
+
+/// Pick the binary to run from those the installer reported. An explicit `--bin` always
+/// wins; otherwise a crate with a single binary uses it, and a crate with several uses the
+/// one named after the crate if there is one.
+fn select_binary(target: &Target, requested: Option<&str>, produced: &[String]) -> Result<String> {
+    if let Some(requested) = requested {
+        return Ok(requested.to_owned());
+    }
+
+    match produced {
+        [] => Ok(target.binary.clone()),
+        [only] => Ok(only.clone()),
+        _ if produced.contains(&target.crate_name) => Ok(target.crate_name.clone()),
+        _ => Err(anyhow!(
+            "{} provides multiple binaries: {}; choose one with --bin <NAME>",
+            target.crate_name,
+            produced.join(", ")
+        )),
+    }
+}
+
+/// Record how the binaries were produced, using the details cargo tracked for the install.
+/// The receipt is shared by every binary of the install, each writing it under its own name.
+fn installation_receipt(
+    target: &Target,
+    binaries: &[String],
+    version: &InstalledVersion,
+    installer: Installer,
+    tracked: Option<&TrackedInstall>,
+    checksum: Option<&str>,
+) -> Receipt {
+    let installed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut receipt = Receipt {
+        crate_name: tracked.map_or_else(
+            || target.crate_name.clone(),
+            |tracked| tracked.crate_name.clone(),
+        ),
+        version: version.clone(),
+        binary: String::new(),
+        binaries: binaries.to_vec(),
+        installer,
+        source: None,
+        registry: target.registry_name().map(str::to_owned),
+        target: None,
+        features: vec![],
+        all_features: false,
+        no_default_features: false,
+        rustc: None,
+        checksum: checksum.map(str::to_owned),
+        installed_at,
+    };
+
+    if let Some(tracked) = tracked {
+        receipt.source = Some(tracked.source.clone());
+        receipt.target = tracked.target.clone();
+        receipt.features = tracked.features.clone();
+        receipt.all_features = tracked.all_features;
+        receipt.no_default_features = tracked.no_default_features;
+        receipt.rustc = tracked
+            .rustc
+            .as_deref()
+            .and_then(|rustc| rustc.lines().next().map(str::to_owned));
+    }
+
+    receipt
 }
 
-fn ensure_bin_dir(install_dir: &Path) -> Result<()> {
+fn ensure_bin_dir(install_dir: &Path) -> Result<PathBuf> {
     let bin_dir = install_dir.join("bin");
-    fs::create_dir_all(&bin_dir).with_context(|| format!("failed to create {}", bin_dir.display()))
+    fs::create_dir_all(&bin_dir)
+        .with_context(|| format!("failed to create {}", bin_dir.display()))?;
+    Ok(bin_dir)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::receipt::read_receipt;
+    use crate::target::{Source, VersionSpec};
+    use crate::versions::{InstalledBinary, test_installed};
+
+    fn target(crate_name: &str) -> Target {
+        Target {
+            crate_name: crate_name.to_owned(),
+            version: VersionSpec::Unspecified,
+            binary: crate_name.to_owned(),
+            source: Source::Registry(None),
+        }
+    }
+
+    fn bins(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn select_binary_uses_single_produced_binary() {
+        let binary = select_binary(&target("ripgrep"), None, &bins(&["rg"])).unwrap();
+        assert_eq!(binary, "rg");
+    }
+
+    #[test]
+    fn select_binary_prefers_explicit_bin() {
+        let binary = select_binary(
+            &target("wasm-bindgen-cli"),
+            Some("wasm-bindgen-test-runner"),
+            &bins(&["wasm-bindgen", "wasm-bindgen-test-runner"]),
+        )
+        .unwrap();
+        assert_eq!(binary, "wasm-bindgen-test-runner");
+    }
+
+    #[test]
+    fn select_binary_prefers_crate_named_binary() {
+        let binary = select_binary(
+            &target("cargo-nextest"),
+            None,
+            &bins(&["cargo-nextest", "nextest-helper"]),
+        )
+        .unwrap();
+        assert_eq!(binary, "cargo-nextest");
+    }
+
+    #[test]
+    fn select_binary_asks_for_bin_when_ambiguous() {
+        let err = select_binary(
+            &target("wasm-bindgen-cli"),
+            None,
+            &bins(&["wasm-bindgen", "wasm-bindgen-test-runner"]),
+        )
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("wasm-bindgen, wasm-bindgen-test-runner"));
+        assert!(message.contains("--bin"));
+    }
+
+    #[test]
+    fn select_binary_falls_back_to_target_binary() {
+        let binary = select_binary(&target("bat"), None, &[]).unwrap();
+        assert_eq!(binary, "bat");
+    }
+
+    fn cli(args: &[&str]) -> Cli {
+        use clap::Parser;
+        Cli::try_parse_from(std::iter::once("cargox").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn finalize_installation_leaves_install_root_alone_on_failure() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let staging = tempfile::tempdir().unwrap();
+        fs::create_dir_all(staging.path().join("bin")).unwrap();
+        fs::write(
+            staging.path().join(".crates2.json"),
+            r#"{"installs":{"wasm-bindgen-cli 0.2.92 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["wasm-bindgen"]}}}"#,
+        )
+        .unwrap();
+        let version: InstalledVersion = "0.2.92".parse().unwrap();
+
+        let err = finalize_installation(
+            install_dir.path(),
+            staging.path(),
+            &target("wasm-bindgen-cli"),
+            &cli(&["--bin", "wasm-bindgen-test-runner", "wasm-bindgen-cli"]),
+            &version,
+            Installer::CargoInstall,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("does not provide"));
+
+        let err = finalize_installation(
+            install_dir.path(),
+            staging.path(),
+            &target("wasm-bindgen-cli"),
+            &cli(&["wasm-bindgen-cli"]),
+            &version,
+            Installer::CargoInstall,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("expected installer to create"));
+
+        assert_eq!(fs::read_dir(install_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn finalize_installation_keeps_every_binary_when_ambiguous() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let staging = tempfile::tempdir().unwrap();
+        let staged_bin_dir = staging.path().join("bin");
+        fs::create_dir_all(&staged_bin_dir).unwrap();
+        fs::write(
+            staging.path().join(".crates2.json"),
+            r#"{"installs":{"wasm-bindgen-cli 0.2.92 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["wasm-bindgen","wasm-bindgen-test-runner"]}}}"#,
+        )
+        .unwrap();
+        for binary in ["wasm-bindgen", "wasm-bindgen-test-runner"] {
+            #[cfg(windows)]
+            let binary = format!("{binary}.exe");
+            fs::write(staged_bin_dir.join(binary), "").unwrap();
+        }
+        let version: InstalledVersion = "0.2.92".parse().unwrap();
+
+        let err = finalize_installation(
+            install_dir.path(),
+            staging.path(),
+            &target("wasm-bindgen-cli"),
+            &cli(&["wasm-bindgen-cli"]),
+            &version,
+            Installer::CargoInstall,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("--bin"));
+
+        let bin_dir = install_dir.path().join("bin");
+        for binary in ["wasm-bindgen", "wasm-bindgen-test-runner"] {
+            let entry = InstalledBinary {
+                path: versioned_binary_path(&bin_dir, binary, &version, None),
+                ..test_installed(binary, "0.2.92")
+            };
+            assert!(entry.path.is_file());
+            let receipt = read_receipt(install_dir.path(), &entry).unwrap();
+            assert_eq!(receipt.unwrap().crate_name, "wasm-bindgen-cli");
+        }
+    }
 
     #[test]
     fn sanitize_cargo_env_removes_cargo_variables() {
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::paths::get_install_dir;
+use crate::receipt::read_receipt;
+use crate::versions::{InstalledBinary, ensure_bin_dir, list_all_installed};
+
+/// Print every versioned binary in the sandbox, grouped by binary name.
+pub fn list_installed() -> Result<()> {
+    let install_dir = get_install_dir()?;
+    let installed = list_all_installed()?;
+
+    if installed.is_empty() {
+        println!("No binaries installed in {}", ensure_bin_dir()?.display());
+        return Ok(());
+    }
+
+    let mut groups: BTreeMap<String, Vec<InstalledBinary>> = BTreeMap::new();
+    for entry in installed {
+        groups.entry(entry.binary.clone()).or_default().push(entry);
+    }
+
+    for (binary, mut versions) in groups {
+        // Newest first; the newest crates.io release is what `cargox <binary>` runs without a
+        // version.
+        versions.reverse();
+        let default = versions
+            .iter()
+            .find(|entry| entry.version.release().is_some() && entry.registry.is_none())
+            .map(|entry| entry.path.clone());
+
+        let mut receipts = Vec::with_capacity(versions.len());
+        for entry in &versions {
+            receipts.push(read_receipt(&install_dir, entry)?);
+        }
+
+        let crate_name = receipts
+            .iter()
+            .flatten()
+            .map(|receipt| receipt.crate_name.clone())
+            .next()
+            .unwrap_or_else(|| "unknown crate".to_string());
+        println!("{binary} ({crate_name})");
+
+        for (entry, receipt) in versions.iter().zip(&receipts) {
+            let metadata = fs::metadata(&entry.path)
+                .with_context(|| format!("failed to read metadata of {}", entry.path.display()))?;
+            let installed_at = match receipt {
+                Some(receipt) => {
+                    format_date(UNIX_EPOCH + Duration::from_secs(receipt.installed_at))
+                }
+                None => metadata
+                    .modified()
+                    .map(format_date)
+                    .unwrap_or_else(|_| "-".to_string()),
+            };
+            println!(
+                "  {:<12} {:<10} {:>10}{}",
+                entry.version_label(),
+                installed_at,
+                format_size(metadata.len()),
+                if default.as_ref() == Some(&entry.path) {
+                    "  (default)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn format_date(time: SystemTime) -> String {
+    let timestamp = humantime::format_rfc3339_seconds(time).to_string();
+    timestamp[..10].to_string()
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(2048), "2.0 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024 + 512 * 1024), "5.5 MiB");
+    }
+
+    #[test]
+    fn format_date_prints_calendar_day() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(format_date(time), "2023-11-14");
+    }
+}
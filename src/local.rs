@@ -0,0 +1,320 @@
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// A crate built from a package directory on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalSource {
+    /// The package directory, holding its `Cargo.toml`.
+    pub dir: PathBuf,
+}
+
+/// Parse a `path:<dir>` spec, relative to `cwd`, into the package name from the directory's
+/// `Cargo.toml` and the source. Returns `None` for specs that do not start with `path:`.
+pub fn parse_path_spec(spec: &str, cwd: &Path) -> Result<Option<(String, LocalSource)>> {
+    let Some(dir) = spec.trim().strip_prefix("path:") else {
+        return Ok(None);
+    };
+    if dir.is_empty() {
+        return Err(anyhow!(
+            "invalid crate spec `{spec}`: expected a directory after `path:`"
+        ));
+    }
+
+    let dir = cwd.join(dir);
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("failed to find {}", dir.display()))?;
+    let name = package_name(&dir.join("Cargo.toml"))?;
+    Ok(Some((name, LocalSource { dir })))
+}
+
+fn package_name(manifest_path: &Path) -> Result<String> {
+    let contents = fs::read_to_string(manifest_path)
+        .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+    let manifest: toml::Table = contents
+        .parse()
+        .with_context(|| format!("failed to parse {}", manifest_path.display()))?;
+
+    manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(|name| name.as_str())
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            anyhow!(
+                "{} has no [package]; point `path:` at the workspace member to run",
+                manifest_path.display()
+            )
+        })
+}
+
+/// A fingerprint of everything that goes into building `source`: the files of the package
+/// and of every local package it depends on, wherever they are, and the workspace's
+/// `Cargo.lock`, so that a change to any of them gives a new fingerprint. When cargo cannot
+/// list the dependencies, every file of the workspace is used instead. Build output in
+/// `target` and hidden files such as `.git` are skipped.
+pub fn fingerprint(source: &LocalSource) -> Result<String> {
+    let (mut roots, lockfile) = match local_dependencies(&source.dir) {
+        Some(local) => local,
+        None => {
+            let root = workspace_root(&source.dir).to_path_buf();
+            let lockfile = root.join("Cargo.lock");
+            (vec![root], lockfile)
+        }
+    };
+    roots.sort();
+    roots.dedup();
+
+    let mut hasher = Sha256::new();
+    for root in &roots {
+        let mut files = vec![];
+        collect_files(root, &mut files)?;
+        files.sort();
+        hash_files(&mut hasher, root, &files)?;
+    }
+    if let Some(workspace) = lockfile.parent()
+        && lockfile.is_file()
+        && !roots.iter().any(|root| workspace.starts_with(root))
+    {
+        hash_files(&mut hasher, workspace, std::slice::from_ref(&lockfile))?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_files(hasher: &mut Sha256, root: &Path, files: &[PathBuf]) -> Result<()> {
+    for file in files {
+        let contents =
+            fs::read(file).with_context(|| format!("failed to read {}", file.display()))?;
+        let relative = file.strip_prefix(root).unwrap_or(file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((contents.len() as u64).to_le_bytes());
+        hasher.update(&contents);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    resolve: Option<MetadataResolve>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct MetadataPackage {
+    id: String,
+    source: Option<String>,
+    manifest_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct MetadataResolve {
+    root: Option<String>,
+    nodes: Vec<MetadataNode>,
+}
+
+#[derive(Deserialize)]
+struct MetadataNode {
+    id: String,
+    dependencies: Vec<String>,
+}
+
+/// The directories of the package in `dir` and of the local packages it depends on,
+/// directly or not, with the path of the workspace's `Cargo.lock`, as `cargo metadata`
+/// reports them. `None` when cargo fails, e.g. offline without the dependencies cached.
+fn local_dependencies(dir: &Path) -> Option<(Vec<PathBuf>, PathBuf)> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(dir.join("Cargo.toml"))
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let metadata: Metadata = serde_json::from_slice(&output.stdout).ok()?;
+    let resolve = metadata.resolve?;
+
+    let local: HashMap<&str, &Path> = metadata
+        .packages
+        .iter()
+        .filter(|package| package.source.is_none())
+        .filter_map(|package| Some((package.id.as_str(), package.manifest_path.parent()?)))
+        .collect();
+    let dependencies: HashMap<&str, &[String]> = resolve
+        .nodes
+        .iter()
+        .map(|node| (node.id.as_str(), node.dependencies.as_slice()))
+        .collect();
+
+    let mut pending = vec![resolve.root.as_deref()?];
+    let mut seen = HashSet::new();
+    let mut roots = vec![];
+    while let Some(id) = pending.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        // Registry and git packages are pinned by `Cargo.lock`.
+        let Some(dir) = local.get(id) else {
+            continue;
+        };
+        roots.push(dir.to_path_buf());
+        pending.extend(
+            dependencies
+                .get(id)
+                .into_iter()
+                .flat_map(|deps| deps.iter().map(String::as_str)),
+        );
+    }
+    Some((roots, metadata.workspace_root.join("Cargo.lock")))
+}
+
+/// The nearest directory at or above `dir` whose `Cargo.toml` declares a `[workspace]`,
+/// or `dir` itself.
+fn workspace_root(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|ancestor| {
+            fs::read_to_string(ancestor.join("Cargo.toml"))
+                .ok()
+                .and_then(|contents| contents.parse::<toml::Table>().ok())
+                .is_some_and(|manifest| manifest.contains_key("workspace"))
+        })
+        .unwrap_or(dir)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", dir.display()))?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') || name == "target" {
+            continue;
+        }
+
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        if file_type.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.is_file() {
+            // Includes symlinked files; symlinked directories are not followed.
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn parse_path_spec_reads_package_name() {
+        let temp = tempdir().unwrap();
+        write(
+            &temp.path().join("tools/codegen/Cargo.toml"),
+            "[package]\nname = \"codegen\"\nversion = \"0.1.0\"\n",
+        );
+        write(
+            &temp.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"tools/*\"]\n",
+        );
+
+        let (name, source) = parse_path_spec("path:./tools/codegen", temp.path())
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "codegen");
+        assert!(source.dir.ends_with("tools/codegen"));
+
+        let err = parse_path_spec("path:.", temp.path()).unwrap_err();
+        assert!(err.to_string().contains("has no [package]"));
+        assert!(parse_path_spec("ripgrep", temp.path()).unwrap().is_none());
+        assert!(parse_path_spec("path:", temp.path()).is_err());
+    }
+
+    #[test]
+    fn fingerprint_follows_workspace_sources() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        write(
+            &root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"tools/*\", \"lib\"]\n",
+        );
+        write(&root.join("Cargo.lock"), "version = 4\n");
+        write(
+            &root.join("tools/codegen/Cargo.toml"),
+            "[package]\nname = \"codegen\"\n",
+        );
+        write(&root.join("tools/codegen/src/main.rs"), "fn main() {}\n");
+        write(&root.join("lib/src/lib.rs"), "pub fn helper() {}\n");
+
+        let source = LocalSource {
+            dir: root.join("tools/codegen"),
+        };
+        let original = fingerprint(&source).unwrap();
+
+        write(&root.join("target/release/codegen"), "binary");
+        write(&root.join(".git/HEAD"), "ref: refs/heads/main\n");
+        assert_eq!(fingerprint(&source).unwrap(), original);
+
+        write(
+            &root.join("lib/src/lib.rs"),
+            "pub fn helper() { todo!() }\n",
+        );
+        let changed = fingerprint(&source).unwrap();
+        assert_ne!(changed, original);
+
+        write(&root.join("Cargo.lock"), "version = 4\n# updated\n");
+        assert_ne!(fingerprint(&source).unwrap(), changed);
+    }
+
+    #[test]
+    fn fingerprint_covers_local_dependencies_outside_the_workspace() {
+        let temp = tempdir().unwrap();
+        let root = temp.path();
+        let package = |name: &str| {
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n")
+        };
+        write(
+            &root.join("ws/Cargo.toml"),
+            "[workspace]\nmembers = [\"tools/app\", \"unrelated\"]\nresolver = \"2\"\n",
+        );
+        write(
+            &root.join("ws/tools/app/Cargo.toml"),
+            &format!(
+                "{}[dependencies]\nhelper = {{ path = \"../../../helper\" }}\n",
+                package("app")
+            ),
+        );
+        write(&root.join("ws/tools/app/src/main.rs"), "fn main() {}\n");
+        write(&root.join("ws/unrelated/Cargo.toml"), &package("unrelated"));
+        write(&root.join("ws/unrelated/src/lib.rs"), "");
+        write(&root.join("helper/Cargo.toml"), &package("helper"));
+        write(&root.join("helper/src/lib.rs"), "pub fn helper() {}\n");
+
+        let source = LocalSource {
+            dir: root.join("ws/tools/app"),
+        };
+        let original = fingerprint(&source).unwrap();
+
+        write(&root.join("ws/unrelated/src/lib.rs"), "pub fn other() {}\n");
+        assert_eq!(fingerprint(&source).unwrap(), original);
+
+        write(
+            &root.join("helper/src/lib.rs"),
+            "pub fn helper() { todo!() }\n",
+        );
+        assert_ne!(fingerprint(&source).unwrap(), original);
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::Path;
+
+use crate::versions::{InstalledVersion, versioned_binary_name};
+
+/// An exclusive advisory lock on a file, released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+    waited: bool,
+}
+
+impl FileLock {
+    /// Whether another process held the lock when it was requested, so that whatever it
+    /// was doing has finished by the time this lock is held.
+    pub fn waited(&self) -> bool {
+        self.waited
+    }
+}
+
+/// Lock held while one version of a crate is installed from a registry, so that concurrent
+/// runs wanting the same version wait for the first installer and then reuse its binary.
+pub fn lock_install(
+    install_dir: &Path,
+    crate_name: &str,
+    version: &InstalledVersion,
+    registry: Option<&str>,
+) -> Result<FileLock> {
+    let path = install_dir.join("locks").join(format!(
+        "{}.lock",
+        versioned_binary_name(crate_name, version, registry)
+    ));
+    lock_file(&path, &format!("{crate_name}@{version}"))
+}
+
+/// Lock held while the shared `bin` directory and cargo's install tracking files are
+/// changed.
+pub fn lock_install_root(install_dir: &Path) -> Result<FileLock> {
+    let path = install_dir.join("locks").join("install-root.lock");
+    lock_file(&path, &install_dir.display().to_string())
+}
+
+/// Take an exclusive lock on `path`, creating it if needed and waiting for any other
+/// process that holds it. `what` names the locked resource in the message shown while
+/// waiting.
+pub fn lock_file(path: &Path, what: &str) -> Result<FileLock> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open lock file {}", path.display()))?;
+
+    let waited = match file.try_lock() {
+        Ok(()) => false,
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("Waiting for another cargox to finish with {what}");
+            file.lock()
+                .with_context(|| format!("failed to lock {}", path.display()))?;
+            true
+        }
+        Err(TryLockError::Error(err)) => {
+            return Err(err).with_context(|| format!("failed to lock {}", path.display()));
+        }
+    };
+
+    Ok(FileLock {
+        _file: file,
+        waited,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn lock_file_waits_for_the_holder() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("locks/tool.lock");
+
+        let held = lock_file(&path, "tool").unwrap();
+        assert!(!held.waited());
+
+        let waiter = {
+            let path = path.clone();
+            thread::spawn(move || lock_file(&path, "tool").unwrap().waited())
+        };
+        thread::sleep(Duration::from_millis(100));
+        drop(held);
+
+        assert!(waiter.join().unwrap());
+        assert!(!lock_file(&path, "tool").unwrap().waited());
+    }
+}
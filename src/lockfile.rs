@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, anyhow};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const LOCKFILE: &str = "cargox.lock";
+
+const LOCKFILE_VERSION: u32 = 1;
+const HEADER: &str =
+    "# This file is automatically generated by cargox.\n# It is not intended for manual editing.\n";
+
+/// Exact tool versions resolved for a project, stored in `cargox.lock` next to its manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    version: u32,
+    #[serde(default, rename = "tool")]
+    tools: Vec<LockedTool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedTool {
+    pub name: String,
+    pub version: Version,
+    /// The alternative registry the version was resolved from; crates.io when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// SHA-256 of the version's `.crate` file, as the registry index listed it when locked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION,
+            tools: vec![],
+        }
+    }
+}
+
+impl Lockfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+        };
+
+        let lockfile: Self = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        if lockfile.version != LOCKFILE_VERSION {
+            return Err(anyhow!(
+                "unsupported lockfile version {} in {}",
+                lockfile.version,
+                path.display()
+            ));
+        }
+        Ok(lockfile)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let body = toml::to_string(self).context("failed to serialize lockfile")?;
+        fs::write(path, format!("{HEADER}{body}"))
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LockedTool> {
+        self.tools.iter().find(|tool| tool.name == name)
+    }
+
+    /// Lock `name` to `version` from `registry`, returning whether the lockfile changed. A
+    /// checksum that is not known leaves the one already recorded for the same version in place.
+    pub fn set(
+        &mut self,
+        name: &str,
+        version: &Version,
+        registry: Option<&str>,
+        checksum: Option<&str>,
+    ) -> bool {
+        if let Some(tool) = self.tools.iter_mut().find(|tool| tool.name == name) {
+            let same_release = &tool.version == version && tool.registry.as_deref() == registry;
+            if same_release && (checksum.is_none() || tool.checksum.as_deref() == checksum) {
+                return false;
+            }
+            if !same_release || checksum.is_some() {
+                tool.checksum = checksum.map(str::to_owned);
+            }
+            tool.version = version.clone();
+            tool.registry = registry.map(str::to_owned);
+            return true;
+        }
+
+        self.tools.push(LockedTool {
+            name: name.to_owned(),
+            version: version.clone(),
+            registry: registry.map(str::to_owned),
+            checksum: checksum.map(str::to_owned),
+        });
+        self.tools.sort_by(|a, b| a.name.cmp(&b.name));
+        true
+    }
+
+    /// Drop entries for tools the manifest no longer pins.
+    pub fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        self.tools.retain(|tool| keep(&tool.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn lockfile_round_trips() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join(LOCKFILE);
+
+        let mut lockfile = Lockfile::load(&path).unwrap();
+        assert!(lockfile.set(
+            "cargo-nextest",
+            &Version::parse("0.9.72").unwrap(),
+            None,
+            None
+        ));
+        assert!(lockfile.set(
+            "cargo-deny",
+            &Version::parse("0.16.3").unwrap(),
+            None,
+            Some("0a1b")
+        ));
+        assert!(!lockfile.set("cargo-deny", &Version::parse("0.16.3").unwrap(), None, None));
+        lockfile.save(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("# This file is automatically generated by cargox."));
+        assert!(contents.find("cargo-deny").unwrap() < contents.find("cargo-nextest").unwrap());
+
+        let loaded = Lockfile::load(&path).unwrap();
+        assert_eq!(loaded, lockfile);
+        assert_eq!(
+            loaded.get("cargo-deny").unwrap().version,
+            Version::parse("0.16.3").unwrap()
+        );
+        assert_eq!(
+            loaded.get("cargo-deny").unwrap().checksum.as_deref(),
+            Some("0a1b")
+        );
+        // Tools locked without a checksum have no `checksum` key.
+        assert_eq!(contents.matches("checksum").count(), 1);
+        assert!(!contents.contains("registry"));
+    }
+
+    #[test]
+    fn set_replaces_checksum_with_version() {
+        let mut lockfile = Lockfile::default();
+        lockfile.set(
+            "ripgrep",
+            &Version::parse("14.1.0").unwrap(),
+            None,
+            Some("aa"),
+        );
+
+        assert!(lockfile.set("ripgrep", &Version::parse("14.1.1").unwrap(), None, None));
+        assert_eq!(lockfile.get("ripgrep").unwrap().checksum, None);
+
+        assert!(lockfile.set(
+            "ripgrep",
+            &Version::parse("14.1.1").unwrap(),
+            None,
+            Some("bb")
+        ));
+        assert_eq!(
+            lockfile.get("ripgrep").unwrap().checksum.as_deref(),
+            Some("bb")
+        );
+    }
+
+    #[test]
+    fn set_replaces_checksum_with_registry() {
+        let version = Version::parse("1.2.0").unwrap();
+        let mut lockfile = Lockfile::default();
+        lockfile.set("deploy-tool", &version, None, Some("aa"));
+
+        assert!(lockfile.set("deploy-tool", &version, Some("internal"), None));
+        let locked = lockfile.get("deploy-tool").unwrap();
+        assert_eq!(locked.registry.as_deref(), Some("internal"));
+        assert_eq!(locked.checksum, None);
+    }
+
+    #[test]
+    fn retain_drops_unpinned_tools() {
+        let mut lockfile = Lockfile::default();
+        lockfile.set("cargo-deny", &Version::parse("0.16.3").unwrap(), None, None);
+        lockfile.set("ripgrep", &Version::parse("14.1.0").unwrap(), None, None);
+
+        lockfile.retain(|name| name == "ripgrep");
+        assert!(lockfile.get("cargo-deny").is_none());
+        assert!(lockfile.get("ripgrep").is_some());
+    }
+}
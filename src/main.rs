@@ -1,44 +1,149 @@
+mod alias;
+mod cache;
+mod cargo_lock;
+mod checksum;
 mod cli;
+mod config;
+mod credentials;
 mod executor;
+mod gc;
+mod git;
 mod installer;
+mod list;
+mod local;
+mod lock;
+mod lockfile;
+mod manifest;
 mod paths;
+mod receipt;
+mod registries;
 mod registry;
+mod script;
 mod target;
+mod tracking;
+mod uninstall;
+mod update;
+mod usage;
+mod verify;
 mod versions;
 
-use std::path::PathBuf;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::process::{ExitStatus, exit};
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use semver::{Version, VersionReq};
 
-use cli::Cli;
-use executor::execute_binary;
-use installer::ensure_installed;
-use paths::resolve_binary_path;
-use registry::{fetch_highest_matching_version, fetch_latest_version};
-use target::{Target, VersionSpec, parse_spec};
-use versions::{find_installed_version, latest_installed, versioned_binary_path};
+use alias::resolve_alias;
+use cargo_lock::{dependency_requirement, lockstep_requirement};
+use cli::{Cli, Command};
+use config::load_config;
+use executor::{KilledBySignal, execute_binary, exit_with_status};
+use gc::{RetentionPolicy, collect_garbage};
+use git::{GitSource, built_binary, is_commit_hash, parse_git_spec, resolve_commit};
+use installer::{ensure_installed, install_git_commit, install_local};
+use list::list_installed;
+use local::{LocalSource, fingerprint, parse_path_spec};
+use lockfile::Lockfile;
+use manifest::{ProjectManifest, find_manifest};
+use paths::{get_install_dir, resolve_binary_path};
+use receipt::{all_receipts, binaries_for_crate};
+use registries::find_registry;
+use registry::{
+    fetch_checksum, fetch_highest_matching_version, fetch_latest_version, is_network_error,
+};
+use script::run_script;
+use target::{Source, Target, VersionSpec, parse_spec, split_registry, validate_crate_name};
+use uninstall::uninstall;
+use update::update_lockfile;
+use usage::record_use;
+use versions::{
+    InstalledBinary, InstalledVersion, find_exact_version, find_installed_version,
+    latest_installed, list_installed_versions,
+};
 
 enum RunPlan {
-    UseInstalled { path: PathBuf },
-    UseSystem { path: PathBuf },
-    InstallAndRun { version: Version },
+    UseInstalled {
+        path: PathBuf,
+        version: InstalledVersion,
+    },
+    UseSystem {
+        path: PathBuf,
+    },
+    /// Install `version`, whose `.crate` must have the `checksum` pinned by `cargox.lock`.
+    InstallAndRun {
+        version: Version,
+        checksum: Option<String>,
+    },
+    /// Build a commit of the target's git repository.
+    BuildCommit {
+        commit: String,
+    },
+    /// Build the target's local sources, whose fingerprint is `version`.
+    BuildLocal {
+        version: InstalledVersion,
+    },
+}
+
+impl RunPlan {
+    /// The release the plan runs, for recording in `cargox.lock`.
+    fn version(&self) -> Option<&Version> {
+        match self {
+            RunPlan::UseInstalled { version, .. } => version.release(),
+            RunPlan::InstallAndRun { version, .. } => Some(version),
+            RunPlan::UseSystem { .. }
+            | RunPlan::BuildCommit { .. }
+            | RunPlan::BuildLocal { .. } => None,
+        }
+    }
 }
 
 fn main() {
-    match run_application() {
+    let cli = match parse_arguments() {
+        Ok(cli) => cli,
+        Err(err) => exit_with_error(err),
+    };
+
+    if let Some(command) = &cli.command {
+        match run_command(command) {
+            Ok(status) => exit_with_status(status),
+            Err(err) => exit_with_error(err),
+        }
+    }
+
+    match run_application(&cli) {
         Ok(status) => exit_with_status(status),
         Err(err) => exit_with_error(err),
     }
 }
 
-fn run_application() -> Result<ExitStatus> {
-    let cli = parse_arguments()?;
-    let target = parse_target_from_cli(&cli)?;
+fn run_application(cli: &Cli) -> Result<ExitStatus> {
+    let target = parse_target_from_cli(cli)?;
 
-    let plan = resolve_run_plan(&target, &cli)?;
-    execute_plan(&plan, &target, &cli)
+    let plan = resolve_run_plan(&target, cli)?;
+    execute_plan(&plan, &target, cli)
+}
+
+fn run_command(command: &Command) -> Result<ExitStatus> {
+    let result = match command {
+        Command::Run { script, args } => return run_script(script, args),
+        Command::List => list_installed(),
+        Command::Uninstall { crate_spec } => uninstall(crate_spec),
+        Command::Gc {
+            keep,
+            unused_for,
+            dry_run,
+        } => {
+            let policy = RetentionPolicy {
+                keep: *keep,
+                unused_days: *unused_for,
+            };
+            collect_garbage(&policy, *dry_run)
+        }
+        Command::Update { tool } => update_lockfile(tool.as_deref()),
+    };
+    result.map(|()| ExitStatus::default())
 }
 
 fn parse_arguments() -> Result<Cli> {
@@ -46,30 +151,210 @@ fn parse_arguments() -> Result<Cli> {
 }
 
 fn parse_target_from_cli(cli: &Cli) -> Result<Target> {
-    let (crate_name, version) = parse_spec(&cli.crate_spec)?;
-    let binary = cli.bin.clone().unwrap_or_else(|| crate_name.clone());
+    let spec = cli
+        .crate_spec
+        .as_deref()
+        .ok_or_else(|| anyhow!("no crate specified"))?;
+    let install_dir = get_install_dir()?;
+
+    let cwd = env::current_dir().context("failed to determine current directory")?;
+
+    let unpublished = match parse_git_spec(spec)? {
+        Some((name, source)) => Some((name, Source::Git(source))),
+        None => parse_path_spec(spec, &cwd)?.map(|(name, source)| (name, Source::Path(source))),
+    };
+    if let Some((name, source)) = unpublished {
+        if cli.registry.is_some() {
+            return Err(anyhow!(
+                "--registry cannot be used with a git or path source"
+            ));
+        }
+        let binary = match &cli.bin {
+            Some(bin) => bin.clone(),
+            None => default_binary(&install_dir, &name)?,
+        };
+        return Ok(Target {
+            crate_name: name,
+            version: VersionSpec::Unspecified,
+            binary,
+            source,
+        });
+    }
+
+    let (spec_registry, spec) = split_registry(spec)?;
+    let (name, version) = parse_spec(spec)?;
+    let config = load_config()?;
+
+    let registry = match (spec_registry, cli.registry.as_deref()) {
+        (Some(from_spec), Some(from_flag)) if from_spec != from_flag => {
+            return Err(anyhow!(
+                "conflicting registries: `{from_spec}` in the crate spec and `{from_flag}` from --registry"
+            ));
+        }
+        (Some(name), _) | (None, Some(name)) => Some(name.to_owned()),
+        (None, None) => None,
+    };
+
+    let (crate_name, binary) = match resolve_alias(&name, &config, &install_dir)? {
+        Some(crate_name) => {
+            validate_crate_name(&crate_name)
+                .with_context(|| format!("alias `{name}` names an invalid crate"))?;
+            if !cli.quiet {
+                eprintln!("Resolved `{name}` to the {crate_name} crate");
+            }
+            let binary = cli.bin.clone().unwrap_or(name);
+            (crate_name, binary)
+        }
+        None => {
+            let binary = match &cli.bin {
+                Some(bin) => bin.clone(),
+                None => default_binary(&install_dir, &name)?,
+            };
+            (name, binary)
+        }
+    };
+
+    // A tool the project pins to a registry is resolved there unless another registry is named
+    // explicitly, so that a crate of the same name on crates.io cannot stand in for it
+    let registry = match registry {
+        Some(name) => Some(name),
+        None => {
+            find_manifest(&cwd)?.and_then(|manifest| manifest.tool(&crate_name)?.registry.clone())
+        }
+    };
+    let registry = registry
+        .map(|name| find_registry(&name, &cwd, &config))
+        .transpose()?;
 
     Ok(Target {
         crate_name,
         version,
         binary,
+        source: Source::Registry(registry),
     })
 }
 
+/// The binary to run when `--bin` is not given: the one earlier installs recorded for the
+/// crate if unambiguous, otherwise a binary named after the crate.
+fn default_binary(install_dir: &Path, crate_name: &str) -> Result<String> {
+    let known = binaries_for_crate(install_dir, crate_name)?;
+    if known.is_empty() || known.contains(crate_name) {
+        return Ok(crate_name.to_owned());
+    }
+    if known.len() == 1 {
+        return Ok(known.into_iter().next().expect("exactly one known binary"));
+    }
+
+    let names: Vec<&str> = known.iter().map(String::as_str).collect();
+    Err(anyhow!(
+        "{crate_name} provides multiple binaries: {}; choose one with --bin <NAME>",
+        names.join(", ")
+    ))
+}
+
 fn resolve_run_plan(target: &Target, cli: &Cli) -> Result<RunPlan> {
+    match &target.source {
+        Source::Git(source) => return resolve_git(target, cli, source),
+        Source::Path(source) => return resolve_local(target, cli, source),
+        Source::Registry(_) => {}
+    }
+
+    let cwd = env::current_dir().context("failed to determine current directory")?;
     match &target.version {
-        VersionSpec::Unspecified => resolve_unspecified(target, cli),
+        VersionSpec::Unspecified => {
+            if let Some(manifest) = project_manifest(&cwd, target)? {
+                return resolve_pinned(target, cli, &manifest);
+            }
+            match lockstep_requirement(&cwd, &target.crate_name)? {
+                Some(requirement) => resolve_requirement(target, cli, &requirement),
+                None => resolve_unspecified(target, cli),
+            }
+        }
         VersionSpec::Latest => resolve_latest(target, cli),
         VersionSpec::Requirement(requirement) => resolve_requirement(target, cli, requirement),
+        VersionSpec::Dependency(package) => {
+            let requirement = dependency_requirement(&cwd, package)?;
+            resolve_requirement(target, cli, &requirement)
+        }
+    }
+}
+
+/// The enclosing project's manifest, if it pins the target in the registry it is resolved
+/// from. Only consulted when no version was given on the command line.
+fn project_manifest(cwd: &Path, target: &Target) -> Result<Option<ProjectManifest>> {
+    let registry = target.registry_name();
+    Ok(find_manifest(cwd)?.filter(|manifest| {
+        manifest
+            .tool(&target.crate_name)
+            .is_some_and(|tool| tool.registry.as_deref() == registry)
+    }))
+}
+
+/// Resolve a tool pinned by the project manifest. A version locked in `cargox.lock` that
+/// still satisfies the pin is used without contacting the registry; otherwise the pin is
+/// resolved as usual and the result is written to the lockfile.
+fn resolve_pinned(target: &Target, cli: &Cli, manifest: &ProjectManifest) -> Result<RunPlan> {
+    let requirement = &manifest
+        .tool(&target.crate_name)
+        .expect("manifest pins the target")
+        .requirement;
+    let registry = target.registry_name();
+    let lockfile_path = manifest.lockfile_path();
+    let mut lockfile = Lockfile::load(&lockfile_path)?;
+
+    if let Some(locked) = lockfile.get(&target.crate_name)
+        && requirement.matches(&locked.version)
+        && locked.registry.as_deref() == registry
+    {
+        return resolve_locked(target, cli, &locked.version, locked.checksum.as_deref());
+    }
+
+    let plan = resolve_requirement(target, cli, requirement)?;
+    if let Some(version) = plan.version() {
+        // The checksum is only a record for later installs, so a registry that cannot be
+        // reached leaves it to the next `cargox update`.
+        let checksum = (!cli.offline)
+            .then(|| fetch_checksum(&target.crate_name, version, target.registry()).ok())
+            .flatten();
+        if lockfile.set(&target.crate_name, version, registry, checksum.as_deref()) {
+            lockfile.save(&lockfile_path)?;
+        }
+    }
+    Ok(plan)
+}
+
+fn resolve_locked(
+    target: &Target,
+    cli: &Cli,
+    version: &Version,
+    checksum: Option<&str>,
+) -> Result<RunPlan> {
+    if !cli.force
+        && let Some(installed) = find_exact_version(
+            &target.binary,
+            target.registry_name(),
+            &version.clone().into(),
+        )?
+    {
+        return Ok(use_installed(installed));
+    }
+
+    if cli.offline {
+        let requirement = VersionReq::parse(&format!("={version}"))
+            .expect("requirement built from a valid version");
+        return Err(not_installed_offline(target, Some(&requirement)));
     }
+
+    Ok(RunPlan::InstallAndRun {
+        version: version.clone(),
+        checksum: checksum.map(str::to_owned),
+    })
 }
 
 fn resolve_unspecified(target: &Target, cli: &Cli) -> Result<RunPlan> {
     if !cli.force {
-        if let Some(installed) = latest_installed(&target.binary)? {
-            return Ok(RunPlan::UseInstalled {
-                path: installed.path,
-            });
+        if let Some(installed) = latest_installed(&target.binary, target.registry_name())? {
+            return Ok(use_installed(installed));
         }
 
         if let Ok(path) = resolve_binary_path(&target.binary) {
@@ -77,64 +362,197 @@ fn resolve_unspecified(target: &Target, cli: &Cli) -> Result<RunPlan> {
         }
     }
 
-    let version = fetch_latest_version(&target.crate_name)?;
-    Ok(RunPlan::InstallAndRun { version })
+    if cli.offline {
+        return Err(not_installed_offline(target, None));
+    }
+
+    let version = fetch_latest_version(&target.crate_name, target.registry())?;
+    Ok(RunPlan::InstallAndRun {
+        version,
+        checksum: None,
+    })
 }
 
 fn resolve_latest(target: &Target, cli: &Cli) -> Result<RunPlan> {
-    let installed = latest_installed(&target.binary)?;
-    let remote = fetch_latest_version(&target.crate_name)?;
+    let installed = latest_installed(&target.binary, target.registry_name())?;
+    if cli.offline {
+        return installed
+            .map(use_installed)
+            .ok_or_else(|| not_installed_offline(target, None));
+    }
+
+    let remote = match fetch_latest_version(&target.crate_name, target.registry()) {
+        Ok(remote) => remote,
+        // Running a slightly stale version beats not running at all when the registry is
+        // unreachable, unless a reinstall was explicitly requested.
+        Err(err) if is_network_error(&err) && !cli.force => match installed {
+            Some(installed) => {
+                eprintln!(
+                    "warning: {err}; running the newest installed {} ({})",
+                    target.binary, installed.version
+                );
+                return Ok(use_installed(installed));
+            }
+            None => return Err(err),
+        },
+        Err(err) => return Err(err),
+    };
 
     if cli.force {
-        return Ok(RunPlan::InstallAndRun { version: remote });
+        return Ok(RunPlan::InstallAndRun {
+            version: remote,
+            checksum: None,
+        });
     }
 
     if let Some(installed) = installed
-        && installed.version >= remote
+        && installed.version.release() >= Some(&remote)
     {
-        return Ok(RunPlan::UseInstalled {
-            path: installed.path,
-        });
+        return Ok(use_installed(installed));
     }
 
-    Ok(RunPlan::InstallAndRun { version: remote })
+    Ok(RunPlan::InstallAndRun {
+        version: remote,
+        checksum: None,
+    })
 }
 
 fn resolve_requirement(target: &Target, cli: &Cli, requirement: &VersionReq) -> Result<RunPlan> {
     if !cli.force
-        && let Some(installed) = find_installed_version(&target.binary, requirement)?
+        && let Some(installed) =
+            find_installed_version(&target.binary, target.registry_name(), requirement)?
     {
-        return Ok(RunPlan::UseInstalled {
-            path: installed.path,
-        });
+        return Ok(use_installed(installed));
+    }
+
+    if cli.offline {
+        return Err(not_installed_offline(target, Some(requirement)));
+    }
+
+    let version =
+        fetch_highest_matching_version(&target.crate_name, Some(requirement), target.registry())?;
+    Ok(RunPlan::InstallAndRun {
+        version,
+        checksum: None,
+    })
+}
+
+/// Resolve a git source to a commit and run the build of that commit. Branches and tags
+/// are looked up on every run so that new commits are picked up; a full commit hash needs
+/// no network access.
+fn resolve_git(target: &Target, cli: &Cli, source: &GitSource) -> Result<RunPlan> {
+    let commit = match &source.rev {
+        Some(rev) if is_commit_hash(rev) => rev.to_ascii_lowercase(),
+        _ if cli.offline => {
+            return Err(anyhow!(
+                "cannot resolve {source} while offline; pass a full commit hash to run an installed build"
+            ));
+        }
+        _ => resolve_commit(source)?,
+    };
+
+    let version = InstalledVersion::git(&commit);
+    if !cli.force {
+        let receipts = all_receipts(&get_install_dir()?)?;
+        let binary = built_binary(&receipts, source, &version, cli.bin.as_deref())
+            .unwrap_or_else(|| target.binary.clone());
+        if let Some(installed) = find_exact_version(&binary, None, &version)? {
+            return Ok(use_installed(installed));
+        }
+    }
+
+    if cli.offline {
+        return Err(anyhow!(
+            "{} {version} is not installed and cannot be built while offline",
+            target.binary
+        ));
+    }
+
+    Ok(RunPlan::BuildCommit { commit })
+}
+
+/// Run the build of a local package's current sources, building them first when they have
+/// changed since the last build.
+fn resolve_local(target: &Target, cli: &Cli, source: &LocalSource) -> Result<RunPlan> {
+    let version = InstalledVersion::path(&fingerprint(source)?);
+    if !cli.force
+        && let Some(installed) =
+            find_exact_version(&target.binary, target.registry_name(), &version)?
+    {
+        return Ok(use_installed(installed));
+    }
+
+    Ok(RunPlan::BuildLocal { version })
+}
+
+fn use_installed(installed: InstalledBinary) -> RunPlan {
+    RunPlan::UseInstalled {
+        path: installed.path,
+        version: installed.version,
     }
+}
+
+/// The error for `--offline` runs that would need to install, listing what is installed so
+/// the user can pick a version that works.
+fn not_installed_offline(target: &Target, requirement: Option<&VersionReq>) -> anyhow::Error {
+    let installed: Vec<String> = list_installed_versions(&target.binary, target.registry_name())
+        .unwrap_or_default()
+        .iter()
+        .map(|entry| entry.version.to_string())
+        .collect();
 
-    let version = fetch_highest_matching_version(&target.crate_name, Some(requirement))?;
-    Ok(RunPlan::InstallAndRun { version })
+    let wanted = match requirement {
+        Some(requirement) => format!("{} {requirement}", target.binary),
+        None => target.binary.clone(),
+    };
+    if installed.is_empty() {
+        anyhow!("{wanted} is not installed and cannot be installed while offline")
+    } else {
+        anyhow!(
+            "{wanted} is not installed and cannot be installed while offline (installed versions: {})",
+            installed.join(", ")
+        )
+    }
 }
 
 fn execute_plan(plan: &RunPlan, target: &Target, cli: &Cli) -> Result<ExitStatus> {
     match plan {
-        RunPlan::UseInstalled { path } => execute_binary(path, &cli.args),
+        RunPlan::UseInstalled { path, .. } => execute_installed(path, &cli.args),
         RunPlan::UseSystem { path } => execute_binary(path, &cli.args),
-        RunPlan::InstallAndRun { version } => {
-            ensure_installed(target, cli, version)?;
-            let binary_path = versioned_binary_path(&target.binary, version)?;
-            execute_binary(&binary_path, &cli.args)
+        RunPlan::InstallAndRun { version, checksum } => {
+            let binary_path = ensure_installed(target, cli, version, checksum.as_deref())?;
+            execute_installed(&binary_path, &cli.args)
+        }
+        RunPlan::BuildCommit { commit } => {
+            let Source::Git(source) = &target.source else {
+                unreachable!("only git sources are built from a commit");
+            };
+            let binary_path = install_git_commit(target, cli, source, commit)?;
+            execute_installed(&binary_path, &cli.args)
+        }
+        RunPlan::BuildLocal { version } => {
+            let Source::Path(source) = &target.source else {
+                unreachable!("only path sources are built locally");
+            };
+            let binary_path = install_local(target, cli, source, version)?;
+            execute_installed(&binary_path, &cli.args)
         }
     }
 }
 
-fn exit_with_status(status: ExitStatus) -> ! {
-    if let Some(code) = status.code() {
-        exit(code);
-    } else {
-        eprintln!("process terminated by signal");
-        exit(1);
+fn execute_installed(path: &Path, args: &[OsString]) -> Result<ExitStatus> {
+    // Usage records only inform `cargox gc`; failing to write one must not stop the run.
+    if let Ok(install_dir) = get_install_dir() {
+        let _ = record_use(&install_dir, path);
     }
+    execute_binary(path, args)
 }
 
 fn exit_with_error(err: anyhow::Error) -> ! {
+    if let Some(killed) = err.downcast_ref::<KilledBySignal>() {
+        exit_with_status(killed.status);
+    }
+
     eprintln!("error: {err}");
     let mut source = err.source();
     while let Some(next) = source {
@@ -0,0 +1,204 @@
+use anyhow::{Context, Result, anyhow};
+use semver::VersionReq;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lockfile::LOCKFILE;
+
+pub const MANIFEST_FILE: &str = "cargox.toml";
+
+/// Tool requirements pinned by a project, from `cargox.toml` or the
+/// `[workspace.metadata.cargox]` table of a `Cargo.toml`.
+#[derive(Debug, Clone)]
+pub struct ProjectManifest {
+    pub path: PathBuf,
+    pub tools: BTreeMap<String, ToolSpec>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolSpec {
+    pub requirement: VersionReq,
+    /// The alternative registry the tool is published to; crates.io when absent.
+    pub registry: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    #[serde(default)]
+    tools: BTreeMap<String, RawTool>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTool {
+    Version(String),
+    Detailed(RawToolTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawToolTable {
+    version: String,
+    registry: Option<String>,
+}
+
+impl ProjectManifest {
+    pub fn tool(&self, crate_name: &str) -> Option<&ToolSpec> {
+        self.tools.get(crate_name)
+    }
+
+    /// `cargox.lock` lives next to the manifest that pins the tools.
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.path.parent().unwrap_or(Path::new(".")).join(LOCKFILE)
+    }
+}
+
+/// Walk up from `start` to the nearest directory that pins tools, either with a
+/// `cargox.toml` or a `Cargo.toml` containing `[workspace.metadata.cargox]`.
+pub fn find_manifest(start: &Path) -> Result<Option<ProjectManifest>> {
+    for dir in start.ancestors() {
+        let path = dir.join(MANIFEST_FILE);
+        if path.is_file() {
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let raw: RawManifest = toml::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            return parse_manifest(path, raw).map(Some);
+        }
+
+        let cargo_toml = dir.join("Cargo.toml");
+        if cargo_toml.is_file()
+            && let Some(raw) = read_workspace_metadata(&cargo_toml)?
+        {
+            return parse_manifest(cargo_toml, raw).map(Some);
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_workspace_metadata(path: &Path) -> Result<Option<RawManifest>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let table: toml::Table = contents
+        .parse()
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let metadata = table
+        .get("workspace")
+        .and_then(|workspace| workspace.get("metadata"))
+        .and_then(|metadata| metadata.get("cargox"));
+    let Some(metadata) = metadata else {
+        return Ok(None);
+    };
+
+    let raw = metadata.clone().try_into().with_context(|| {
+        format!(
+            "failed to parse [workspace.metadata.cargox] in {}",
+            path.display()
+        )
+    })?;
+    Ok(Some(raw))
+}
+
+fn parse_manifest(path: PathBuf, raw: RawManifest) -> Result<ProjectManifest> {
+    let mut tools = BTreeMap::new();
+    for (name, tool) in raw.tools {
+        let (version, registry) = match tool {
+            RawTool::Version(version) => (version, None),
+            RawTool::Detailed(table) => (table.version, table.registry),
+        };
+        let requirement = VersionReq::parse(&version).map_err(|err| {
+            anyhow!(
+                "invalid version requirement `{version}` for {name} in {}: {err}",
+                path.display()
+            )
+        })?;
+        tools.insert(
+            name,
+            ToolSpec {
+                requirement,
+                registry,
+            },
+        );
+    }
+
+    Ok(ProjectManifest { path, tools })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn find_manifest_walks_up_to_cargox_toml() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join(MANIFEST_FILE),
+            "[tools]\ncargo-deny = \"0.16\"\ncargo-nextest = { version = \"=0.9.72\" }\ndeploy-tool = { version = \"1.2\", registry = \"internal\" }\n",
+        )
+        .unwrap();
+        let nested = temp.path().join("crates").join("app");
+        fs::create_dir_all(&nested).unwrap();
+
+        let manifest = find_manifest(&nested).unwrap().unwrap();
+        assert_eq!(manifest.path, temp.path().join(MANIFEST_FILE));
+        assert_eq!(manifest.lockfile_path(), temp.path().join(LOCKFILE));
+        assert_eq!(
+            manifest.tool("cargo-deny").unwrap().requirement.to_string(),
+            "^0.16"
+        );
+        assert_eq!(
+            manifest
+                .tool("cargo-nextest")
+                .unwrap()
+                .requirement
+                .to_string(),
+            "=0.9.72"
+        );
+        assert_eq!(manifest.tool("cargo-deny").unwrap().registry, None);
+        assert_eq!(
+            manifest.tool("deploy-tool").unwrap().registry.as_deref(),
+            Some("internal")
+        );
+        assert!(manifest.tool("ripgrep").is_none());
+    }
+
+    #[test]
+    fn find_manifest_reads_workspace_metadata() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"app\"]\n\n[workspace.metadata.cargox.tools]\ncargo-deny = \"0.16\"\n",
+        )
+        .unwrap();
+        let member = temp.path().join("app");
+        fs::create_dir_all(&member).unwrap();
+        fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let manifest = find_manifest(&member).unwrap().unwrap();
+        assert_eq!(manifest.path, temp.path().join("Cargo.toml"));
+        assert!(manifest.tool("cargo-deny").is_some());
+    }
+
+    #[test]
+    fn find_manifest_rejects_invalid_requirements() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join(MANIFEST_FILE),
+            "[tools]\ncargo-deny = \"not a version\"\n",
+        )
+        .unwrap();
+
+        let err = find_manifest(temp.path()).unwrap_err();
+        assert!(err.to_string().contains("cargo-deny"));
+    }
+}
@@ -33,6 +33,36 @@ pub fn get_install_dir() -> Result<PathBuf> {
     Err(anyhow!("unable to determine install directory"))
 }
 
+/// Location of the cargox configuration file. `CARGOX_CONFIG` overrides the platform
+/// default (e.g. `~/.config/cargox/config.toml` on Linux).
+pub fn get_config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("CARGOX_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+
+    if let Some(proj_dirs) = ProjectDirs::from("", "", "cargox") {
+        return Some(proj_dirs.config_dir().join("config.toml"));
+    }
+
+    home_dir().map(|home| home.join(".config").join("cargox").join("config.toml"))
+}
+
+/// Directory for data cargox can always refetch, such as registry metadata.
+/// `CARGOX_CACHE_DIR` overrides the platform default (e.g. `~/.cache/cargox` on Linux).
+pub fn get_cache_dir() -> Result<PathBuf> {
+    if let Some(path) = env::var_os("CARGOX_CACHE_DIR") {
+        return Ok(PathBuf::from(path));
+    }
+
+    if let Some(proj_dirs) = ProjectDirs::from("", "", "cargox") {
+        return Ok(proj_dirs.cache_dir().to_path_buf());
+    }
+
+    home_dir()
+        .map(|home| home.join(".cache").join("cargox"))
+        .ok_or_else(|| anyhow!("unable to determine cache directory"))
+}
+
 pub fn resolve_binary_path(name: &str) -> Result<PathBuf> {
     if let Ok(path) = which::which(name) {
         return Ok(path);
@@ -67,6 +97,12 @@ fn candidate_bin_dirs() -> Vec<PathBuf> {
     dirs
 }
 
+/// The Cargo home the installers use. `CARGO_HOME` is removed from their environment, so
+/// this is always `~/.cargo`.
+pub fn cargo_home() -> Option<PathBuf> {
+    home_dir().map(|home| home.join(".cargo"))
+}
+
 fn home_dir() -> Option<PathBuf> {
     env::var_os("HOME")
         .or_else(|| env::var_os("USERPROFILE"))
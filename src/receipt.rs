@@ -0,0 +1,220 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::versions::{InstalledBinary, InstalledVersion, versioned_binary_name};
+
+/// The tool that produced an installed binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Installer {
+    CargoBinstall,
+    CargoInstall,
+}
+
+/// Provenance of a versioned binary, written to `receipts/<binary>-<version>.json` in the
+/// install directory when the binary is installed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub crate_name: String,
+    pub version: InstalledVersion,
+    pub binary: String,
+    /// Every binary installed from the crate alongside this one, including itself.
+    #[serde(default)]
+    pub binaries: Vec<String>,
+    pub installer: Installer,
+    /// Package source as recorded by cargo, e.g. `registry+https://github.com/rust-lang/crates.io-index`.
+    pub source: Option<String>,
+    /// Name of the alternative registry the crate was installed from; unset for crates.io.
+    #[serde(default)]
+    pub registry: Option<String>,
+    pub target: Option<String>,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub all_features: bool,
+    #[serde(default)]
+    pub no_default_features: bool,
+    pub rustc: Option<String>,
+    /// SHA-256 of the `.crate` file, as the registry index lists it. Only set for releases
+    /// from a registry; source builds have checked that the crate cargo built matches it.
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub installed_at: u64,
+}
+
+/// A minimal receipt for tests; vary it with struct update syntax.
+#[cfg(test)]
+pub fn test_receipt(crate_name: &str, binary: &str, version: &str) -> Receipt {
+    Receipt {
+        crate_name: crate_name.into(),
+        version: version.parse().unwrap(),
+        binary: binary.into(),
+        binaries: vec![binary.into()],
+        installer: Installer::CargoBinstall,
+        source: None,
+        registry: None,
+        target: None,
+        features: vec![],
+        all_features: false,
+        no_default_features: false,
+        rustc: None,
+        checksum: None,
+        installed_at: 0,
+    }
+}
+
+impl Receipt {
+    /// Whether this is the receipt of the installed binary `entry`.
+    pub fn describes(&self, entry: &InstalledBinary) -> bool {
+        self.binary == entry.binary
+            && self.version == entry.version
+            && self.registry == entry.registry
+    }
+}
+
+pub fn receipt_path(
+    install_dir: &Path,
+    binary: &str,
+    version: &InstalledVersion,
+    registry: Option<&str>,
+) -> PathBuf {
+    install_dir.join("receipts").join(format!(
+        "{}.json",
+        versioned_binary_name(binary, version, registry)
+    ))
+}
+
+pub fn write_receipt(install_dir: &Path, receipt: &Receipt) -> Result<()> {
+    let path = receipt_path(
+        install_dir,
+        &receipt.binary,
+        &receipt.version,
+        receipt.registry.as_deref(),
+    );
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let contents = serde_json::to_string_pretty(receipt).context("failed to serialize receipt")?;
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn read_receipt(install_dir: &Path, entry: &InstalledBinary) -> Result<Option<Receipt>> {
+    let path = receipt_path(
+        install_dir,
+        &entry.binary,
+        &entry.version,
+        entry.registry.as_deref(),
+    );
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context(format!("failed to read {}", path.display())),
+    };
+
+    let receipt = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    Ok(Some(receipt))
+}
+
+pub fn remove_receipt(install_dir: &Path, entry: &InstalledBinary) -> Result<()> {
+    let path = receipt_path(
+        install_dir,
+        &entry.binary,
+        &entry.version,
+        entry.registry.as_deref(),
+    );
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).context(format!("failed to remove {}", path.display())),
+    }
+}
+
+/// Every receipt in the install directory. Unreadable receipts are skipped.
+pub fn all_receipts(install_dir: &Path) -> Result<Vec<Receipt>> {
+    let dir = install_dir.join("receipts");
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).context(format!("failed to read {}", dir.display())),
+    };
+
+    let mut receipts = Vec::new();
+    for entry in entries {
+        let entry = entry.context("failed to iterate receipts")?;
+        let Ok(contents) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        if let Ok(receipt) = serde_json::from_str(&contents) {
+            receipts.push(receipt);
+        }
+    }
+    Ok(receipts)
+}
+
+/// The binaries receipts record for `crate_name`.
+pub fn binaries_for_crate(install_dir: &Path, crate_name: &str) -> Result<BTreeSet<String>> {
+    Ok(all_receipts(install_dir)?
+        .into_iter()
+        .filter(|receipt| receipt.crate_name == crate_name)
+        .map(|receipt| receipt.binary)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::versions::test_installed;
+    use tempfile::tempdir;
+
+    fn sample() -> Receipt {
+        Receipt {
+            source: Some("registry+https://github.com/rust-lang/crates.io-index".into()),
+            target: Some("x86_64-unknown-linux-gnu".into()),
+            checksum: Some(
+                "9a4d9a0fb2a6e3a1a9d1b7a1f2b5a6c5d4c3b2a1f0e9d8c7b6a5f4e3d2c1b0a9".into(),
+            ),
+            installed_at: 1_700_000_000,
+            ..test_receipt("ripgrep", "rg", "14.1.0")
+        }
+    }
+
+    #[test]
+    fn receipts_round_trip() {
+        let temp = tempdir().unwrap();
+        let receipt = sample();
+
+        let entry = test_installed("rg", "14.1.0");
+        write_receipt(temp.path(), &receipt).unwrap();
+        let path = receipt_path(temp.path(), "rg", &receipt.version, None);
+        assert!(path.ends_with("receipts/rg-14.1.0.json"));
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\"installer\": \"cargo-binstall\""));
+
+        let read = read_receipt(temp.path(), &entry).unwrap();
+        assert_eq!(read, Some(receipt.clone()));
+        assert!(receipt.describes(&entry));
+        let internal = InstalledBinary {
+            registry: Some("internal".into()),
+            ..entry.clone()
+        };
+        assert!(!receipt.describes(&internal));
+        assert!(read_receipt(temp.path(), &internal).unwrap().is_none());
+        assert_eq!(all_receipts(temp.path()).unwrap(), vec![receipt.clone()]);
+        assert!(
+            binaries_for_crate(temp.path(), "ripgrep")
+                .unwrap()
+                .contains("rg")
+        );
+
+        remove_receipt(temp.path(), &entry).unwrap();
+        assert!(read_receipt(temp.path(), &entry).unwrap().is_none());
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, ProviderCommand};
+
+/// Cargo's default when no credential provider is configured.
+const DEFAULT_CREDENTIAL_PROVIDER: &str = "cargo:token";
+
+/// A registry other than crates.io, as defined in Cargo's or cargox's configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltRegistry {
+    pub name: String,
+    /// The sparse index URL, without the `sparse+` prefix or a trailing slash.
+    pub index_url: String,
+    pub defined_in: DefinedIn,
+    /// Credential provider commands to ask for a token, highest precedence first.
+    pub credential_providers: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinedIn {
+    /// A `[registries]` entry in Cargo's `config.toml`, which the installers can read
+    /// themselves.
+    Cargo,
+    /// A `[registries]` entry in the cargox config, unknown to Cargo.
+    Cargox,
+}
+
+impl AltRegistry {
+    /// Arguments telling `cargo install` and `cargo binstall` to install from this registry.
+    /// Cargo only knows registries from its own config by name, so the others are passed
+    /// by index URL.
+    pub fn install_args(&self) -> [String; 2] {
+        match self.defined_in {
+            DefinedIn::Cargo => ["--registry".into(), self.name.clone()],
+            DefinedIn::Cargox => ["--index".into(), format!("sparse+{}", self.index_url)],
+        }
+    }
+}
+
+/// The environment variable Cargo reads for `registries.<name>.<key>`, e.g.
+/// `CARGO_REGISTRIES_MY_REGISTRY_TOKEN`.
+pub fn registry_env_var(name: &str, key: &str) -> String {
+    format!(
+        "CARGO_REGISTRIES_{}_{key}",
+        name.to_uppercase().replace('-', "_")
+    )
+}
+
+/// Look up the registry `name`, preferring Cargo's definition (from
+/// `CARGO_REGISTRIES_<NAME>_INDEX` or a `config.toml` Cargo would read from `cwd`) over
+/// one in the cargox config.
+pub fn find_registry(name: &str, cwd: &Path, config: &Config) -> Result<AltRegistry> {
+    let cargo_config = CargoConfig::load(&cargo_config_files(cwd))?;
+    let mut registry = find_registry_in(name, &cargo_config, config)?;
+
+    if let Ok(index) = env::var(registry_env_var(name, "INDEX")) {
+        registry.index_url = sparse_index_url(name, &index)?;
+        registry.defined_in = DefinedIn::Cargo;
+    }
+    if let Ok(provider) = env::var(registry_env_var(name, "CREDENTIAL_PROVIDER")) {
+        registry.credential_providers = vec![cargo_config.expand_alias(split_command(&provider))];
+    }
+    Ok(registry)
+}
+
+fn find_registry_in(
+    name: &str,
+    cargo_config: &CargoConfig,
+    config: &Config,
+) -> Result<AltRegistry> {
+    let cargo_index = cargo_config.get(&["registries", name, "index"]);
+    let (index, defined_in, provider) = match (cargo_index, config.registries.get(name)) {
+        (Some(index), _) => {
+            let index = index
+                .as_str()
+                .ok_or_else(|| anyhow!("registries.{name}.index must be a string"))?;
+            let provider = cargo_config
+                .get(&["registries", name, "credential-provider"])
+                .map(|value| command_from_value(value, name))
+                .transpose()?;
+            (index.to_owned(), DefinedIn::Cargo, provider)
+        }
+        (None, Some(registry)) => {
+            let provider = registry
+                .credential_provider
+                .as_ref()
+                .map(ProviderCommand::to_args);
+            (registry.index.clone(), DefinedIn::Cargox, provider)
+        }
+        (None, None) => {
+            return Err(anyhow!(
+                "registry `{name}` is not defined; add it to [registries] in Cargo's config.toml or the cargox config"
+            ));
+        }
+    };
+
+    let credential_providers = match provider {
+        Some(provider) => vec![cargo_config.expand_alias(provider)],
+        None => cargo_config.global_credential_providers()?,
+    };
+
+    Ok(AltRegistry {
+        name: name.to_owned(),
+        index_url: sparse_index_url(name, &index)?,
+        defined_in,
+        credential_providers,
+    })
+}
+
+/// The Cargo config files that apply in `cwd`, most specific first. `CARGO_HOME` is not
+/// consulted because cargox runs the installers without it.
+fn cargo_config_files(cwd: &Path) -> Vec<PathBuf> {
+    let home = env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+
+    cwd.ancestors()
+        .map(Path::to_path_buf)
+        .chain(home)
+        .flat_map(|dir| {
+            let cargo_dir = dir.join(".cargo");
+            [cargo_dir.join("config.toml"), cargo_dir.join("config")]
+        })
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// The parsed Cargo config files, most specific first. A key takes its value from the
+/// first file that sets it, as Cargo's own merging does for scalar values.
+struct CargoConfig {
+    tables: Vec<toml::Table>,
+}
+
+impl CargoConfig {
+    fn load(paths: &[PathBuf]) -> Result<Self> {
+        let tables = paths
+            .iter()
+            .map(|path| {
+                let contents = fs::read_to_string(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                contents
+                    .parse()
+                    .with_context(|| format!("failed to parse {}", path.display()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { tables })
+    }
+
+    fn get(&self, keys: &[&str]) -> Option<&toml::Value> {
+        self.tables.iter().find_map(|table| {
+            let (first, rest) = keys.split_first()?;
+            rest.iter()
+                .try_fold(table.get(*first)?, |value, key| value.get(*key))
+        })
+    }
+
+    /// `registry.global-credential-providers`, highest precedence first. Cargo gives later
+    /// entries precedence, so the configured list is reversed.
+    fn global_credential_providers(&self) -> Result<Vec<Vec<String>>> {
+        let Some(value) = self.get(&["registry", "global-credential-providers"]) else {
+            return Ok(vec![vec![DEFAULT_CREDENTIAL_PROVIDER.to_owned()]]);
+        };
+        let providers = value
+            .as_array()
+            .ok_or_else(|| anyhow!("registry.global-credential-providers must be an array"))?;
+
+        providers
+            .iter()
+            .rev()
+            .map(|provider| {
+                let provider = provider.as_str().ok_or_else(|| {
+                    anyhow!("registry.global-credential-providers must contain strings")
+                })?;
+                Ok(self.expand_alias(split_command(provider)))
+            })
+            .collect()
+    }
+
+    /// Replace a provider naming a `[credential-alias]` entry with the aliased command.
+    fn expand_alias(&self, provider: Vec<String>) -> Vec<String> {
+        let [name] = provider.as_slice() else {
+            return provider;
+        };
+        match self.get(&["credential-alias", name]) {
+            Some(toml::Value::String(command)) => split_command(command),
+            Some(toml::Value::Array(args)) => args
+                .iter()
+                .filter_map(|arg| arg.as_str().map(str::to_owned))
+                .collect(),
+            _ => provider,
+        }
+    }
+}
+
+fn command_from_value(value: &toml::Value, name: &str) -> Result<Vec<String>> {
+    match value {
+        toml::Value::String(command) => Ok(split_command(command)),
+        toml::Value::Array(args) => args
+            .iter()
+            .map(|arg| {
+                arg.as_str().map(str::to_owned).ok_or_else(|| {
+                    anyhow!("registries.{name}.credential-provider must contain strings")
+                })
+            })
+            .collect(),
+        _ => Err(anyhow!(
+            "registries.{name}.credential-provider must be a string or an array"
+        )),
+    }
+}
+
+/// Cargo splits string-valued commands on whitespace; arrays are used when an argument
+/// contains spaces.
+fn split_command(command: &str) -> Vec<String> {
+    command.split_whitespace().map(str::to_owned).collect()
+}
+
+fn sparse_index_url(name: &str, index: &str) -> Result<String> {
+    let index_url = index.strip_prefix("sparse+").ok_or_else(|| {
+        anyhow!(
+            "registry `{name}` uses a git index ({index}); only sparse registries (`sparse+https://…`) are supported"
+        )
+    })?;
+    Ok(index_url.trim_end_matches('/').to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RegistryConfig;
+    use tempfile::tempdir;
+
+    fn cargo_config(contents: &str) -> CargoConfig {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("config.toml");
+        fs::write(&path, contents).unwrap();
+        CargoConfig::load(&[path]).unwrap()
+    }
+
+    #[test]
+    fn cargo_config_takes_precedence_over_cargox_config() {
+        let cargo_config = cargo_config(
+            "[registries.internal]\nindex = \"sparse+https://cargo.example.com/index/\"\n",
+        );
+
+        let mut config = Config::default();
+        config.registries.insert(
+            "internal".into(),
+            RegistryConfig {
+                index: "sparse+https://cargox.example.com/index".into(),
+                credential_provider: None,
+            },
+        );
+        config.registries.insert(
+            "other".into(),
+            RegistryConfig {
+                index: "sparse+https://other.example.com/".into(),
+                credential_provider: Some(ProviderCommand::Command(
+                    "cargo:token-from-stdout pass show other".into(),
+                )),
+            },
+        );
+
+        let internal = find_registry_in("internal", &cargo_config, &config).unwrap();
+        assert_eq!(internal.index_url, "https://cargo.example.com/index");
+        assert_eq!(internal.install_args(), ["--registry", "internal"]);
+        assert_eq!(internal.credential_providers, vec![vec!["cargo:token"]]);
+
+        let other = find_registry_in("other", &cargo_config, &config).unwrap();
+        assert_eq!(other.defined_in, DefinedIn::Cargox);
+        assert_eq!(
+            other.install_args(),
+            ["--index", "sparse+https://other.example.com"]
+        );
+        assert_eq!(
+            other.credential_providers,
+            vec![vec!["cargo:token-from-stdout", "pass", "show", "other"]]
+        );
+    }
+
+    #[test]
+    fn credential_providers_follow_cargo_precedence() {
+        let cargo_config = cargo_config(
+            r#"
+[registry]
+global-credential-providers = ["cargo:token", "my-provider --flag"]
+
+[credential-alias]
+vault = ["vault-credential", "--path", "secret/cargo"]
+
+[registries.internal]
+index = "sparse+https://cargo.example.com/index/"
+
+[registries.aliased]
+index = "sparse+https://aliased.example.com/index/"
+credential-provider = "vault"
+"#,
+        );
+        let config = Config::default();
+
+        let internal = find_registry_in("internal", &cargo_config, &config).unwrap();
+        assert_eq!(
+            internal.credential_providers,
+            vec![vec!["my-provider", "--flag"], vec!["cargo:token"]]
+        );
+
+        let aliased = find_registry_in("aliased", &cargo_config, &config).unwrap();
+        assert_eq!(
+            aliased.credential_providers,
+            vec![vec!["vault-credential", "--path", "secret/cargo"]]
+        );
+    }
+
+    #[test]
+    fn unknown_and_git_registries_are_rejected() {
+        let cargo_config =
+            cargo_config("[registries.legacy]\nindex = \"https://github.com/example/index\"\n");
+
+        let config = Config::default();
+        let err = find_registry_in("legacy", &cargo_config, &config).unwrap_err();
+        assert!(err.to_string().contains("only sparse registries"));
+        let err = find_registry_in("missing", &cargo_config, &config).unwrap_err();
+        assert!(err.to_string().contains("is not defined"));
+    }
+}
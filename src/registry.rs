@@ -1,9 +1,20 @@
 use anyhow::{Context, Result, anyhow};
+use reqwest::StatusCode;
 use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::header::{
+    AUTHORIZATION, ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    USER_AGENT,
+};
 use semver::{Version, VersionReq};
 use serde::Deserialize;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache::{CachedResponse, MetadataCache};
+use crate::credentials::registry_token;
+use crate::registries::AltRegistry;
+
+const CRATES_IO_INDEX: &str = "https://index.crates.io";
+const CRATES_IO_API: &str = "https://crates.io/api/v1/crates";
 
 #[derive(Deserialize)]
 struct VersionsResponse {
@@ -14,16 +25,267 @@ struct VersionsResponse {
 struct CrateVersion {
     num: String,
     yanked: bool,
+    #[serde(default)]
+    checksum: Option<String>,
+}
+
+/// One line of a sparse index file, describing a single published version.
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: String,
+    #[serde(default)]
+    yanked: bool,
+    /// SHA-256 of the version's `.crate` file.
+    #[serde(default)]
+    cksum: Option<String>,
+}
+
+/// A sparse index's `config.json`.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct IndexConfig {
+    #[serde(default)]
+    auth_required: bool,
 }
 
 pub fn fetch_highest_matching_version(
     crate_name: &str,
     requirement: Option<&VersionReq>,
+    registry: Option<&AltRegistry>,
 ) -> Result<Version> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-        .context("failed to build HTTP client")?;
+    let versions = fetch_published_versions(crate_name, registry)?;
+    select_version(crate_name, versions, requirement)
+}
+
+pub fn fetch_latest_version(crate_name: &str, registry: Option<&AltRegistry>) -> Result<Version> {
+    fetch_highest_matching_version(crate_name, None, registry)
+}
+
+/// The SHA-256 checksum of the `.crate` file of `crate_name` at `version`, as the registry
+/// index lists it. Yanked versions are included, since a lockfile may still pin one.
+pub fn fetch_checksum(
+    crate_name: &str,
+    version: &Version,
+    registry: Option<&AltRegistry>,
+) -> Result<String> {
+    let cache = MetadataCache::open()?;
+    // A version published since the index was cached is not in it yet, so a miss is looked
+    // up again with the cached copy revalidated.
+    let checksum = match lookup_checksum(&cache, crate_name, version, registry)? {
+        Some(checksum) => Some(checksum),
+        None => lookup_checksum(&cache.revalidating(), crate_name, version, registry)?,
+    };
+    checksum.ok_or_else(|| anyhow!("the registry lists no checksum for {crate_name}@{version}"))
+}
+
+fn lookup_checksum(
+    cache: &MetadataCache,
+    crate_name: &str,
+    version: &Version,
+    registry: Option<&AltRegistry>,
+) -> Result<Option<String>> {
+    match registry {
+        Some(registry) => fetch_registry_index(cache, registry, crate_name)
+            .map(|body| index_checksum(&body, version))
+            .with_context(|| {
+                format!(
+                    "failed to look up {crate_name} in registry `{}`",
+                    registry.name
+                )
+            }),
+        None => fetch_index_file(cache, CRATES_IO_INDEX, crate_name, None)
+            .map(|body| index_checksum(&body, version))
+            .or_else(|index_err| {
+                fetch_api_response(cache, crate_name)
+                    .map(|payload| api_checksum(payload, version))
+                    .map_err(|_| index_err)
+            }),
+    }
+}
+
+/// The sparse index URL of `registry`, or of crates.io.
+pub fn index_url(registry: Option<&AltRegistry>) -> &str {
+    registry.map_or(CRATES_IO_INDEX, |registry| &registry.index_url)
+}
+
+/// Whether a registry lookup failed because the registry could not be reached, as opposed
+/// to the crate or a matching version not existing.
+pub fn is_network_error(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.status().is_none_or(|status| status.is_server_error()))
+}
+
+/// The crate's unyanked versions. For crates.io the sparse index is small and served from
+/// a CDN, so it is asked first; the API is only used when the index cannot answer.
+/// Alternative registries are only reached through their sparse index.
+fn fetch_published_versions(
+    crate_name: &str,
+    registry: Option<&AltRegistry>,
+) -> Result<Vec<Version>> {
+    let cache = MetadataCache::open()?;
+    match registry {
+        Some(registry) => fetch_registry_index(&cache, registry, crate_name)
+            .map(|body| parse_index_file(&body))
+            .with_context(|| {
+                format!(
+                    "failed to resolve {crate_name} in registry `{}`",
+                    registry.name
+                )
+            }),
+        None => fetch_index_file(&cache, CRATES_IO_INDEX, crate_name, None)
+            .map(|body| parse_index_file(&body))
+            .or_else(|index_err| fetch_api_versions(&cache, crate_name).map_err(|_| index_err)),
+    }
+}
+
+/// The crate's file from an alternative registry's index, authenticating when the
+/// registry requires it.
+fn fetch_registry_index(
+    cache: &MetadataCache,
+    registry: &AltRegistry,
+    crate_name: &str,
+) -> Result<String> {
+    let token = if index_requires_auth(cache, registry)? {
+        Some(registry_token(registry)?)
+    } else {
+        None
+    };
+    fetch_index_file(cache, &registry.index_url, crate_name, token.as_deref())
+}
+
+/// Whether the registry wants a token, either by setting `auth-required` in its
+/// `config.json` or by refusing to serve that file without one.
+fn index_requires_auth(cache: &MetadataCache, registry: &AltRegistry) -> Result<bool> {
+    let result = fetch_cached(
+        cache,
+        &format!("{}/config.json", index_cache_key(&registry.index_url)),
+        &format!("{}/config.json", registry.index_url),
+        "the registry index",
+        None,
+    );
+
+    match result {
+        Ok(body) => {
+            let config: IndexConfig = serde_json::from_str(&body)
+                .context("failed to parse the registry's config.json")?;
+            Ok(config.auth_required)
+        }
+        Err(err) if is_unauthorized(&err) => Ok(true),
+        Err(err) => Err(err),
+    }
+}
+
+fn is_unauthorized(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(StatusCode::UNAUTHORIZED)
+}
+
+fn fetch_index_file(
+    cache: &MetadataCache,
+    index_url: &str,
+    crate_name: &str,
+    token: Option<&str>,
+) -> Result<String> {
+    let path = index_path(crate_name);
+    fetch_cached(
+        cache,
+        &format!("{}/{path}", index_cache_key(index_url)),
+        &format!("{}/{path}", index_url.trim_end_matches('/')),
+        "the registry index",
+        token,
+    )
+}
+
+/// The cache key prefix for an index: its URL without the scheme.
+fn index_cache_key(index_url: &str) -> &str {
+    index_url
+        .split("://")
+        .nth(1)
+        .unwrap_or(index_url)
+        .trim_end_matches('/')
+}
+
+fn fetch_api_response(cache: &MetadataCache, crate_name: &str) -> Result<VersionsResponse> {
+    let body = fetch_cached(
+        cache,
+        &format!("crates.io/{crate_name}"),
+        &format!("{CRATES_IO_API}/{crate_name}"),
+        "crates.io",
+        None,
+    )?;
+    serde_json::from_str(&body).context("failed to parse crates.io response")
+}
+
+fn fetch_api_versions(cache: &MetadataCache, crate_name: &str) -> Result<Vec<Version>> {
+    let payload = fetch_api_response(cache, crate_name)?;
+
+    Ok(payload
+        .versions
+        .into_iter()
+        .filter(|v| !v.yanked)
+        .filter_map(|entry| Version::parse(&entry.num).ok())
+        .collect())
+}
+
+/// The path of a crate's file within a sparse index, following Cargo's layout: `1/a`,
+/// `2/ab`, `3/a/abc`, and `ab/cd/abcd…` for longer names, all lowercased. The name must
+/// have passed [`crate::target::validate_crate_name`], so it is ASCII and can be sliced by byte.
+fn index_path(crate_name: &str) -> String {
+    let name = crate_name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{name}"),
+        2 => format!("2/{name}"),
+        3 => format!("3/{}/{name}", &name[..1]),
+        _ => format!("{}/{}/{name}", &name[..2], &name[2..4]),
+    }
+}
+
+/// Unyanked versions listed in a sparse index file. Lines that cannot be parsed, such as
+/// entries using a newer index schema, are skipped.
+fn parse_index_file(body: &str) -> Vec<Version> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok())
+        .filter(|entry| !entry.yanked)
+        .filter_map(|entry| Version::parse(&entry.vers).ok())
+        .collect()
+}
+
+/// The checksum a sparse index file lists for `version`.
+fn index_checksum(body: &str, version: &Version) -> Option<String> {
+    body.lines()
+        .filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok())
+        .find(|entry| Version::parse(&entry.vers).is_ok_and(|vers| vers == *version))
+        .and_then(|entry| entry.cksum)
+}
+
+fn api_checksum(payload: VersionsResponse, version: &Version) -> Option<String> {
+    payload
+        .versions
+        .into_iter()
+        .find(|entry| Version::parse(&entry.num).is_ok_and(|num| num == *version))
+        .and_then(|entry| entry.checksum)
+}
+
+/// GET `url`, going through the on-disk cache under `key`. A fresh entry is used as is,
+/// and a stale one is revalidated with its `ETag`/`Last-Modified` so an unchanged crate
+/// costs only a `304 Not Modified`. `token` is sent as-is in `Authorization`, as Cargo does.
+fn fetch_cached(
+    cache: &MetadataCache,
+    key: &str,
+    url: &str,
+    what: &str,
+    token: Option<&str>,
+) -> Result<String> {
+    let now = unix_now();
+
+    let cached = cache.get(key);
+    if let Some(entry) = &cached
+        && cache.is_fresh(entry, now)
+    {
+        return Ok(entry.body.clone());
+    }
 
     let mut headers = HeaderMap::new();
     let user_agent = format!("cargox/{}", env!("CARGO_PKG_VERSION"));
@@ -31,28 +293,90 @@ pub fn fetch_highest_matching_version(
         USER_AGENT,
         HeaderValue::from_str(&user_agent).context("invalid user agent")?,
     );
+    if let Some(token) = token {
+        let mut value = HeaderValue::from_str(token).context("invalid registry token")?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    if let Some(entry) = &cached {
+        if let Some(value) = entry
+            .etag
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_NONE_MATCH, value);
+        }
+        if let Some(value) = entry
+            .last_modified
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, value);
+        }
+    }
 
-    let url = format!("https://crates.io/api/v1/crates/{crate_name}");
-
-    let response = client
+    let response = http_client()?
         .get(url)
         .headers(headers)
         .send()
-        .context("failed to contact crates.io")?
+        .with_context(|| format!("failed to contact {what}"))?;
+
+    // The cache only saves time; failing to update it must not fail the lookup.
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let Some(mut entry) = cached
+    {
+        entry.fetched_at = now;
+        let _ = cache.put(key, &entry);
+        return Ok(entry.body);
+    }
+
+    let response = response
         .error_for_status()
-        .context("crates.io returned an error status")?;
+        .with_context(|| format!("{what} returned an error status"))?;
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let body = response
+        .text()
+        .with_context(|| format!("failed to read the response from {what}"))?;
 
-    let payload: VersionsResponse = response
-        .json()
-        .context("failed to parse crates.io response")?;
+    let _ = cache.put(
+        key,
+        &CachedResponse {
+            fetched_at: now,
+            etag,
+            last_modified,
+            body: body.clone(),
+        },
+    );
+    Ok(body)
+}
 
-    let mut versions: Vec<Version> = payload
-        .versions
-        .into_iter()
-        .filter(|v| !v.yanked)
-        .filter_map(|entry| Version::parse(&entry.num).ok())
-        .collect();
+fn http_client() -> Result<Client> {
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .context("failed to build HTTP client")
+}
 
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+fn select_version(
+    crate_name: &str,
+    mut versions: Vec<Version>,
+    requirement: Option<&VersionReq>,
+) -> Result<Version> {
     if versions.is_empty() {
         return Err(anyhow!("no published versions found for {crate_name}"));
     }
@@ -74,6 +398,81 @@ pub fn fetch_highest_matching_version(
         .ok_or_else(|| anyhow!("no published versions found for {crate_name}"))
 }
 
-pub fn fetch_latest_version(crate_name: &str) -> Result<Version> {
-    fetch_highest_matching_version(crate_name, None)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn versions(nums: &[&str]) -> Vec<Version> {
+        nums.iter()
+            .map(|num| Version::parse(num).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn index_path_follows_cargo_layout() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("fd"), "2/fd");
+        assert_eq!(index_path("bat"), "3/b/bat");
+        assert_eq!(index_path("ripgrep"), "ri/pg/ripgrep");
+        assert_eq!(index_path("Inflector"), "in/fl/inflector");
+    }
+
+    #[test]
+    fn parse_index_file_skips_yanked_and_unknown_lines() {
+        let body = concat!(
+            r#"{"name":"demo","vers":"1.0.0","deps":[],"cksum":"aa","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"demo","vers":"1.1.0","deps":[],"cksum":"bb","features":{},"yanked":true}"#,
+            "\n",
+            r#"{"name":"demo","vers":"1.2.0","deps":[],"cksum":"cc","features":{},"yanked":false,"rust_version":"1.70"}"#,
+            "\n",
+            "not json\n",
+        );
+
+        assert_eq!(parse_index_file(body), versions(&["1.0.0", "1.2.0"]));
+    }
+
+    #[test]
+    fn index_checksum_includes_yanked_versions() {
+        let body = concat!(
+            r#"{"name":"demo","vers":"1.0.0","deps":[],"cksum":"aa","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"demo","vers":"1.1.0","deps":[],"cksum":"bb","features":{},"yanked":true}"#,
+            "\n",
+        );
+
+        let version = |v: &str| Version::parse(v).unwrap();
+        assert_eq!(
+            index_checksum(body, &version("1.0.0")).as_deref(),
+            Some("aa")
+        );
+        assert_eq!(
+            index_checksum(body, &version("1.1.0")).as_deref(),
+            Some("bb")
+        );
+        assert_eq!(index_checksum(body, &version("2.0.0")), None);
+    }
+
+    #[test]
+    fn select_version_picks_highest_match() {
+        let available = versions(&["1.2.0", "2.0.0", "1.10.1"]);
+        let req = VersionReq::parse("1").unwrap();
+
+        assert_eq!(
+            select_version("demo", available.clone(), Some(&req)).unwrap(),
+            Version::parse("1.10.1").unwrap()
+        );
+        assert_eq!(
+            select_version("demo", available, None).unwrap(),
+            Version::parse("2.0.0").unwrap()
+        );
+    }
+
+    #[test]
+    fn select_version_reports_unsatisfied_requirements() {
+        let req = VersionReq::parse("3").unwrap();
+        let err = select_version("demo", versions(&["1.0.0"]), Some(&req)).unwrap_err();
+        assert!(err.to_string().contains("satisfy requirement ^3"));
+        assert!(select_version("demo", vec![], None).is_err());
+    }
 }
@@ -0,0 +1,357 @@
+use anyhow::{Context, Result, anyhow};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::executor::{child_failure, execute_binary, run_to_completion, termination_signal};
+use crate::local::{LocalSource, fingerprint};
+use crate::lock::lock_file;
+use crate::paths::get_cache_dir;
+
+/// Edition used when a script's manifest does not choose one.
+const DEFAULT_EDITION: &str = "2024";
+
+/// Build the single-file Rust script at `path` if needed and run it with `args`.
+///
+/// Each distinct script is built once, in a directory under the cargox cache named after
+/// a hash of its generated manifest, its source and the sources of its `path`
+/// dependencies, so running an unchanged script only costs hashing them. Builds share one
+/// target directory so dependencies are compiled once.
+pub fn run_script(path: &Path, args: &[OsString]) -> Result<ExitStatus> {
+    let script = fs::read_to_string(path)
+        .with_context(|| format!("failed to read script {}", path.display()))?;
+    let script_dir = path
+        .canonicalize()
+        .with_context(|| format!("failed to find script {}", path.display()))?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let name = package_name(path);
+
+    let (frontmatter, source) =
+        split_frontmatter(&script).with_context(|| format!("invalid script {}", path.display()))?;
+    let manifest = script_manifest(frontmatter.as_deref(), &name, &script_dir)
+        .with_context(|| format!("invalid manifest in {}", path.display()))?;
+
+    let scripts_dir = get_cache_dir()?.join("scripts");
+    let build_dir = scripts_dir.join(content_hash(&manifest, &source)?);
+    let binary = build_dir.join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    if !binary.is_file() {
+        // Scripts with the same name build to the same path in the shared target
+        // directory, so they are built one at a time.
+        let lock_path = scripts_dir.join("locks").join(format!("{name}.lock"));
+        let _lock = lock_file(&lock_path, &path.display().to_string())?;
+        if !binary.is_file() {
+            build_script(
+                path,
+                &name,
+                &build_dir,
+                &scripts_dir.join("target"),
+                &manifest,
+                &source,
+            )?;
+            copy_binary(&scripts_dir.join("target"), &name, &binary)?;
+        }
+    }
+
+    execute_binary(&binary, args)
+}
+
+/// Split a script into its `---cargo` frontmatter and the Rust source that follows. The
+/// frontmatter may only be preceded by a `#!` line and blank lines, and is replaced by
+/// blank lines in the returned source so that compiler errors point at the right line.
+fn split_frontmatter(script: &str) -> Result<(Option<String>, String)> {
+    let lines: Vec<&str> = script.split_inclusive('\n').collect();
+    let shebang = lines
+        .first()
+        .is_some_and(|line| line.starts_with("#!") && !line.starts_with("#!["));
+    let first = usize::from(shebang);
+
+    let Some(open) = (first..lines.len()).find(|&idx| !lines[idx].trim().is_empty()) else {
+        return Ok((None, script.to_owned()));
+    };
+    let fence_len = lines[open].chars().take_while(|&c| c == '-').count();
+    if fence_len < 3 {
+        return Ok((None, script.to_owned()));
+    }
+
+    let info = lines[open][fence_len..].trim();
+    if !info.is_empty() && info != "cargo" {
+        return Err(anyhow!(
+            "unsupported frontmatter `{info}`; only `cargo` manifests are supported"
+        ));
+    }
+
+    let fence = &lines[open][..fence_len];
+    let close = (open + 1..lines.len())
+        .find(|&idx| lines[idx].trim_end() == fence)
+        .ok_or_else(|| anyhow!("frontmatter opened with `{fence}` is never closed"))?;
+
+    let frontmatter = lines[open + 1..close].concat();
+    let mut source = lines[..open].concat();
+    for line in &lines[open..=close] {
+        if line.ends_with('\n') {
+            source.push('\n');
+        }
+    }
+    source.push_str(&lines[close + 1..].concat());
+    Ok((Some(frontmatter), source))
+}
+
+/// The `Cargo.toml` for a script: its frontmatter completed with a package named after the
+/// script, a single binary, and its own workspace so that an enclosing one is not picked
+/// up. Relative `path` dependencies are resolved against the script's directory.
+fn script_manifest(frontmatter: Option<&str>, name: &str, script_dir: &Path) -> Result<String> {
+    let mut manifest: toml::Table = frontmatter
+        .unwrap_or_default()
+        .parse()
+        .context("failed to parse the frontmatter")?;
+
+    for key in ["bin", "lib", "example", "test", "bench", "workspace"] {
+        if manifest.contains_key(key) {
+            return Err(anyhow!("scripts cannot declare `[{key}]`"));
+        }
+    }
+
+    let package = manifest
+        .entry("package")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("`package` must be a table"))?;
+    package
+        .entry("name")
+        .or_insert_with(|| name.to_owned().into());
+    package
+        .entry("version")
+        .or_insert_with(|| "0.0.0".to_owned().into());
+    package
+        .entry("edition")
+        .or_insert_with(|| DEFAULT_EDITION.to_owned().into());
+    package.insert("publish".into(), false.into());
+
+    for key in ["dependencies", "build-dependencies"] {
+        if let Some(dependencies) = manifest.get_mut(key).and_then(toml::Value::as_table_mut) {
+            resolve_path_dependencies(dependencies, script_dir);
+        }
+    }
+
+    let mut bin = toml::Table::new();
+    bin.insert("name".into(), name.to_owned().into());
+    bin.insert("path".into(), format!("{name}.rs").into());
+    manifest.insert("bin".into(), toml::Value::Array(vec![bin.into()]));
+    manifest.insert("workspace".into(), toml::Table::new().into());
+
+    toml::to_string(&manifest).context("failed to write the script manifest")
+}
+
+fn resolve_path_dependencies(dependencies: &mut toml::Table, script_dir: &Path) {
+    for (_, dependency) in dependencies.iter_mut() {
+        if let Some(path) = dependency.get_mut("path").filter(|path| {
+            path.as_str()
+                .is_some_and(|path| Path::new(path).is_relative())
+        }) {
+            let absolute = script_dir.join(path.as_str().unwrap_or_default());
+            *path = absolute.to_string_lossy().into_owned().into();
+        }
+    }
+}
+
+/// The package and binary name for a script, derived from its file name the way Cargo
+/// does for scripts.
+fn package_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut name: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// Identifies a build of a script. `path` dependencies are included by the fingerprint of
+/// their sources, since a change to them is not visible in the manifest.
+fn content_hash(manifest: &str, source: &str) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hasher.update(manifest.as_bytes());
+    hasher.update([0]);
+    hasher.update(source.as_bytes());
+    for dir in path_dependencies(manifest)? {
+        let fingerprint = fingerprint(&LocalSource { dir: dir.clone() })
+            .with_context(|| format!("failed to read path dependency {}", dir.display()))?;
+        hasher.update([0]);
+        hasher.update(fingerprint.as_bytes());
+    }
+    let digest = format!("{:x}", hasher.finalize());
+    Ok(digest[..16].to_owned())
+}
+
+/// The directories of the `path` dependencies in a generated script manifest, which are
+/// absolute by then.
+fn path_dependencies(manifest: &str) -> Result<Vec<PathBuf>> {
+    let manifest: toml::Table = manifest
+        .parse()
+        .context("failed to parse the script manifest")?;
+    let mut dirs = vec![];
+    for key in ["dependencies", "build-dependencies"] {
+        let Some(dependencies) = manifest.get(key).and_then(toml::Value::as_table) else {
+            continue;
+        };
+        dirs.extend(
+            dependencies
+                .values()
+                .filter_map(|dependency| dependency.get("path")?.as_str())
+                .map(PathBuf::from),
+        );
+    }
+    Ok(dirs)
+}
+
+fn build_script(
+    path: &Path,
+    name: &str,
+    build_dir: &Path,
+    target_dir: &Path,
+    manifest: &str,
+    source: &str,
+) -> Result<()> {
+    fs::create_dir_all(build_dir)
+        .with_context(|| format!("failed to create {}", build_dir.display()))?;
+    let manifest_path = build_dir.join("Cargo.toml");
+    fs::write(&manifest_path, manifest)
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+    // Named after the script so that compiler diagnostics mention it.
+    let source_path = build_dir.join(format!("{name}.rs"));
+    fs::write(&source_path, source)
+        .with_context(|| format!("failed to write {}", source_path.display()))?;
+
+    eprintln!("Compiling {}", path.display());
+    let mut cmd = Command::new("cargo");
+    cmd.args(["build", "--quiet", "--manifest-path"])
+        .arg(&manifest_path)
+        .env("CARGO_TARGET_DIR", target_dir);
+    let status = run_to_completion(&mut cmd).context("failed to invoke cargo build")?;
+    if termination_signal(status).is_some() {
+        return Err(child_failure("cargo build", status));
+    }
+    if !status.success() {
+        return Err(anyhow!("failed to compile {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Copy the freshly built binary next to the script's manifest. The copy is written under
+/// a temporary name and renamed so that a concurrent run never sees a partial binary.
+fn copy_binary(target_dir: &Path, name: &str, binary: &Path) -> Result<()> {
+    let built: PathBuf = target_dir
+        .join("debug")
+        .join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    let partial = binary.with_extension("partial");
+    fs::copy(&built, &partial).with_context(|| format!("failed to copy {}", built.display()))?;
+    fs::rename(&partial, binary)
+        .with_context(|| format!("failed to move {} into place", binary.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_frontmatter_blanks_out_the_manifest() {
+        let script = "#!/usr/bin/env -S cargox run\n---cargo\n[dependencies]\nregex = \"1\"\n---\n\nfn main() {}\n";
+        let (frontmatter, source) = split_frontmatter(script).unwrap();
+        assert_eq!(
+            frontmatter.as_deref(),
+            Some("[dependencies]\nregex = \"1\"\n")
+        );
+        assert_eq!(
+            source,
+            "#!/usr/bin/env -S cargox run\n\n\n\n\n\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn split_frontmatter_handles_scripts_without_one() {
+        let script = "#![allow(dead_code)]\nfn main() {}\n";
+        let (frontmatter, source) = split_frontmatter(script).unwrap();
+        assert!(frontmatter.is_none());
+        assert_eq!(source, script);
+    }
+
+    #[test]
+    fn split_frontmatter_rejects_bad_fences() {
+        assert!(split_frontmatter("---toml\n---\n").is_err());
+        assert!(split_frontmatter("----\n[package]\n---\n").is_err());
+        assert!(split_frontmatter("----\n---\n----\n").is_ok());
+    }
+
+    #[test]
+    fn script_manifest_completes_the_package() {
+        let manifest = script_manifest(
+            Some("[dependencies]\nhelpers = { path = \"../helpers\" }\nregex = \"1\"\n"),
+            "tidy",
+            Path::new("/work/scripts"),
+        )
+        .unwrap();
+        let manifest: toml::Table = manifest.parse().unwrap();
+
+        assert_eq!(manifest["package"]["name"].as_str(), Some("tidy"));
+        assert_eq!(manifest["package"]["edition"].as_str(), Some("2024"));
+        assert_eq!(manifest["bin"][0]["path"].as_str(), Some("tidy.rs"));
+        assert!(manifest.contains_key("workspace"));
+        let helpers = Path::new("/work/scripts").join("../helpers");
+        assert_eq!(
+            manifest["dependencies"]["helpers"]["path"].as_str(),
+            helpers.to_str()
+        );
+        assert_eq!(manifest["dependencies"]["regex"].as_str(), Some("1"));
+
+        let err =
+            script_manifest(Some("[[bin]]\nname = \"x\"\n"), "x", Path::new("/")).unwrap_err();
+        assert!(err.to_string().contains("cannot declare"));
+    }
+
+    #[test]
+    fn content_hash_tracks_path_dependencies() {
+        let temp = tempfile::tempdir().unwrap();
+        let helpers = temp.path().join("helpers");
+        fs::create_dir_all(helpers.join("src")).unwrap();
+        fs::write(
+            helpers.join("Cargo.toml"),
+            "[package]\nname = \"helpers\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        fs::write(helpers.join("src").join("lib.rs"), "pub fn one() {}\n").unwrap();
+        let manifest = script_manifest(
+            Some("[dependencies]\nhelpers = { path = \"helpers\" }\n"),
+            "tidy",
+            temp.path(),
+        )
+        .unwrap();
+
+        let before = content_hash(&manifest, "fn main() {}").unwrap();
+        assert_eq!(content_hash(&manifest, "fn main() {}").unwrap(), before);
+
+        fs::write(helpers.join("src").join("lib.rs"), "pub fn two() {}\n").unwrap();
+        assert_ne!(content_hash(&manifest, "fn main() {}").unwrap(), before);
+    }
+
+    #[test]
+    fn package_name_follows_file_name() {
+        assert_eq!(package_name(Path::new("scripts/tidy-up.rs")), "tidy-up");
+        assert_eq!(package_name(Path::new("2fa.rs")), "_2fa");
+        assert_eq!(package_name(Path::new("release notes.rs")), "release_notes");
+    }
+}
@@ -1,11 +1,40 @@
 use anyhow::{Result, anyhow};
 use semver::VersionReq;
 
+use crate::git::GitSource;
+use crate::local::LocalSource;
+use crate::registries::AltRegistry;
+
 #[derive(Debug)]
 pub struct Target {
     pub crate_name: String,
     pub version: VersionSpec,
     pub binary: String,
+    pub source: Source,
+}
+
+/// Where a target is resolved and installed from.
+#[derive(Debug)]
+pub enum Source {
+    /// crates.io when `None`, otherwise an alternative registry.
+    Registry(Option<AltRegistry>),
+    Git(GitSource),
+    Path(LocalSource),
+}
+
+impl Target {
+    /// The alternative registry to resolve and install from, if any.
+    pub fn registry(&self) -> Option<&AltRegistry> {
+        match &self.source {
+            Source::Registry(registry) => registry.as_ref(),
+            Source::Git(_) | Source::Path(_) => None,
+        }
+    }
+
+    /// The name of the alternative registry, which keys its installs apart from crates.io.
+    pub fn registry_name(&self) -> Option<&str> {
+        self.registry().map(|registry| registry.name.as_str())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +42,39 @@ pub enum VersionSpec {
     Unspecified,
     Latest,
     Requirement(VersionReq),
+    /// `@lock:<package>`: the version of `package` in the nearest `Cargo.lock`.
+    Dependency(String),
+}
+
+/// Split a `registry:crate[@version]` spec into the registry name and the rest.
+pub fn split_registry(spec: &str) -> Result<(Option<&str>, &str)> {
+    let name_end = spec.find('@').unwrap_or(spec.len());
+    let Some(colon) = spec[..name_end].find(':') else {
+        return Ok((None, spec));
+    };
+
+    let registry = spec[..colon].trim();
+    if registry.is_empty() {
+        return Err(anyhow!(
+            "invalid crate spec `{spec}`: registry name cannot be empty before `:`"
+        ));
+    }
+    Ok((Some(registry), &spec[colon + 1..]))
+}
+
+/// Check that `name` is a valid crate name: ASCII letters, digits, `-` and `_`, starting
+/// with a letter, as crates.io requires.
+pub fn validate_crate_name(name: &str) -> Result<()> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(anyhow!(
+            "invalid crate name `{name}`: expected ASCII letters, digits, `-` and `_`, starting with a letter"
+        ));
+    }
+    Ok(())
 }
 
 pub fn parse_spec(spec: &str) -> Result<(String, VersionSpec)> {
@@ -29,6 +91,7 @@ pub fn parse_spec(spec: &str) -> Result<(String, VersionSpec)> {
     if first.is_empty() {
         return Err(anyhow!("crate name cannot be empty"));
     }
+    validate_crate_name(first)?;
 
     let rest: Vec<&str> = parts.collect();
     if rest.is_empty() {
@@ -52,6 +115,19 @@ pub fn parse_spec(spec: &str) -> Result<(String, VersionSpec)> {
         return Ok((first.to_owned(), VersionSpec::Latest));
     }
 
+    if let Some(package) = version.strip_prefix("lock:") {
+        let package = package.trim();
+        if package.is_empty() {
+            return Err(anyhow!(
+                "invalid crate spec `{spec}`: expected a package name after `lock:`"
+            ));
+        }
+        return Ok((
+            first.to_owned(),
+            VersionSpec::Dependency(package.to_owned()),
+        ));
+    }
+
     let requirement = VersionReq::parse(version).map_err(|err| {
         anyhow!(
             "invalid crate spec `{spec}`: failed to parse version requirement `{version}`: {err}"
@@ -92,6 +168,41 @@ mod tests {
         assert!(parse_spec("foo@bar@baz").is_err());
     }
 
+    #[test]
+    fn split_spec_rejects_invalid_crate_names() {
+        assert!(parse_spec("aéb").is_err());
+        assert!(parse_spec("été@1.0").is_err());
+        assert!(parse_spec("1tool").is_err());
+        assert!(parse_spec("my.tool").is_err());
+        assert!(parse_spec("cargo_nextest-2").is_ok());
+    }
+
+    #[test]
+    fn split_spec_parses_lock_dependency() {
+        let (name, version) = parse_spec("wasm-bindgen-cli@lock:wasm-bindgen").unwrap();
+        assert_eq!(name, "wasm-bindgen-cli");
+        match version {
+            VersionSpec::Dependency(package) => assert_eq!(package, "wasm-bindgen"),
+            other => panic!("unexpected version spec: {other:?}"),
+        }
+
+        assert!(parse_spec("wasm-bindgen-cli@lock:").is_err());
+    }
+
+    #[test]
+    fn split_registry_prefix() {
+        assert_eq!(
+            split_registry("internal:tool@1.2").unwrap(),
+            (Some("internal"), "tool@1.2")
+        );
+        assert_eq!(split_registry("tool@1.2").unwrap(), (None, "tool@1.2"));
+        assert_eq!(
+            split_registry("tool@lock:dep").unwrap(),
+            (None, "tool@lock:dep")
+        );
+        assert!(split_registry(":tool").is_err());
+    }
+
     #[test]
     fn split_spec_parses_latest() {
         let (name, version) = parse_spec("ripgrep@latest").unwrap();
@@ -0,0 +1,191 @@
+use anyhow::{Context, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::versions::InstalledVersion;
+
+/// A crate recorded in the `.crates2.json` file that `cargo install` and `cargo-binstall`
+/// maintain in an install root. cargox reads it from the staging root of each install, to
+/// learn what the install produced; its own install root has no tracking files.
+#[derive(Debug, Clone)]
+pub struct TrackedInstall {
+    pub crate_name: String,
+    pub version: Version,
+    pub source: String,
+    pub bins: Vec<String>,
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub target: Option<String>,
+    pub rustc: Option<String>,
+}
+
+impl TrackedInstall {
+    /// Whether this install is the build identified by `version`.
+    pub fn is_build(&self, version: &InstalledVersion) -> bool {
+        is_build(&self.version, &self.source, version)
+    }
+}
+
+#[derive(Deserialize)]
+struct CratesV2 {
+    #[serde(default)]
+    installs: BTreeMap<String, CratesV2Entry>,
+}
+
+#[derive(Deserialize)]
+struct CratesV2Entry {
+    #[serde(default)]
+    bins: Vec<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    all_features: bool,
+    #[serde(default)]
+    no_default_features: bool,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    rustc: Option<String>,
+}
+
+pub fn tracked_installs(install_dir: &Path) -> Result<Vec<TrackedInstall>> {
+    let path = install_dir.join(".crates2.json");
+    let Some(contents) = read_optional(&path)? else {
+        return Ok(vec![]);
+    };
+
+    let manifest: CratesV2 = serde_json::from_str(&contents)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    Ok(manifest
+        .installs
+        .into_iter()
+        .filter_map(|(id, entry)| {
+            let (crate_name, version, source) = parse_package_id(&id)?;
+            let bins = entry
+                .bins
+                .into_iter()
+                .map(|bin| strip_exe_suffix(&bin).to_owned())
+                .collect();
+            Some(TrackedInstall {
+                crate_name,
+                version,
+                source,
+                bins,
+                features: entry.features,
+                all_features: entry.all_features,
+                no_default_features: entry.no_default_features,
+                target: entry.target,
+                rustc: entry.rustc,
+            })
+        })
+        .collect())
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).context(format!("failed to read {}", path.display())),
+    }
+}
+
+/// Releases are identified by their version; git builds by the commit cargo records at the
+/// end of the source, e.g. `git+https://github.com/org/tool?rev=main#<commit>`. Cargo keeps
+/// nothing that identifies a local build, so any install from a path matches one.
+fn is_build(version: &Version, source: &str, build: &InstalledVersion) -> bool {
+    match build {
+        InstalledVersion::Release(release) => {
+            !source.starts_with("git+") && !source.starts_with("path+") && version == release
+        }
+        InstalledVersion::Git(commit) => {
+            source.starts_with("git+")
+                && source
+                    .rsplit_once('#')
+                    .is_some_and(|(_, full)| full.starts_with(commit.as_str()))
+        }
+        InstalledVersion::Path(_) => source.starts_with("path+"),
+    }
+}
+
+/// Split a cargo package id of the form `name version (source)`.
+fn parse_package_id(id: &str) -> Option<(String, Version, String)> {
+    let mut parts = id.splitn(3, ' ');
+    let name = parts.next()?;
+    let version = Version::parse(parts.next()?).ok()?;
+    let source = parts
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('(')
+        .trim_end_matches(')');
+    Some((name.to_owned(), version, source.to_owned()))
+}
+
+fn strip_exe_suffix(bin: &str) -> &str {
+    bin.strip_suffix(".exe").unwrap_or(bin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_package_id_splits_components() {
+        let (name, version, source) = parse_package_id(
+            "ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+        )
+        .unwrap();
+        assert_eq!(name, "ripgrep");
+        assert_eq!(version, Version::parse("14.1.0").unwrap());
+        assert_eq!(
+            source,
+            "registry+https://github.com/rust-lang/crates.io-index"
+        );
+    }
+
+    #[test]
+    fn tracked_installs_reads_crates2_json() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join(".crates2.json"),
+            r#"{"installs":{"ripgrep 14.1.0 (registry+https://github.com/rust-lang/crates.io-index)":{"bins":["rg.exe"],"features":["pcre2"],"target":"x86_64-unknown-linux-gnu","profile":"release"}}}"#,
+        )
+        .unwrap();
+
+        let installs = tracked_installs(temp.path()).unwrap();
+        assert_eq!(installs.len(), 1);
+        assert_eq!(installs[0].crate_name, "ripgrep");
+        assert_eq!(installs[0].bins, vec!["rg".to_string()]);
+        assert_eq!(installs[0].features, vec!["pcre2".to_string()]);
+        assert_eq!(
+            installs[0].target.as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+    }
+
+    #[test]
+    fn git_builds_are_identified_by_commit() {
+        let temp = tempdir().unwrap();
+        fs::write(
+            temp.path().join(".crates2.json"),
+            r#"{"installs":{"tool 0.3.0 (git+https://github.com/org/tool?branch=main#0123456789abcdef0123456789abcdef01234567)":{"bins":["tool"]}}}"#,
+        )
+        .unwrap();
+
+        let installs = tracked_installs(temp.path()).unwrap();
+        assert!(installs[0].is_build(&InstalledVersion::git("0123456789abcdef")));
+        assert!(!installs[0].is_build(&InstalledVersion::git("fedcba987654")));
+        assert!(!installs[0].is_build(&Version::parse("0.3.0").unwrap().into()));
+    }
+
+    #[test]
+    fn tracked_installs_handles_missing_file() {
+        let temp = tempdir().unwrap();
+        assert!(tracked_installs(temp.path()).unwrap().is_empty());
+    }
+}
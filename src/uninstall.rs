@@ -0,0 +1,180 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::cargo_lock::dependency_requirement;
+use crate::lock::lock_install_root;
+use crate::paths::get_install_dir;
+use crate::receipt::{Receipt, all_receipts, remove_receipt};
+use crate::target::{VersionSpec, parse_spec, split_registry};
+use crate::usage::forget;
+use crate::versions::{InstalledBinary, list_all_installed};
+
+/// Remove every installed binary of the crate matching `spec`. A `registry:` prefix selects
+/// the installs from that registry, as it does when running; without one, installs from
+/// crates.io and local builds are removed.
+pub fn uninstall(spec: &str) -> Result<()> {
+    let (registry, spec) = split_registry(spec)?;
+    let (crate_name, version) = parse_spec(spec)?;
+    let install_dir = get_install_dir()?;
+
+    let receipts = all_receipts(&install_dir)?;
+    let installed: Vec<InstalledBinary> = list_all_installed()?
+        .into_iter()
+        .filter(|entry| entry.registry.as_deref() == registry)
+        .filter(|entry| is_from_crate(&receipts, entry, &crate_name))
+        .collect();
+
+    let version = match version {
+        VersionSpec::Dependency(package) => {
+            let cwd = env::current_dir().context("failed to determine current directory")?;
+            VersionSpec::Requirement(dependency_requirement(&cwd, &package)?)
+        }
+        version => version,
+    };
+
+    let selected = select_versions(installed, &version);
+    if selected.is_empty() {
+        return Err(anyhow!(
+            "no installed versions of {crate_name} match `{spec}`"
+        ));
+    }
+
+    for entry in &selected {
+        remove_installed(&install_dir, entry)?;
+        eprintln!("Removed {} {}", entry.binary, entry.version_label());
+    }
+
+    Ok(())
+}
+
+/// Remove a versioned binary along with its usage record and receipt.
+pub fn remove_installed(install_dir: &Path, entry: &InstalledBinary) -> Result<()> {
+    let _root = lock_install_root(install_dir)?;
+    fs::remove_file(&entry.path)
+        .with_context(|| format!("failed to remove {}", entry.path.display()))?;
+    forget(install_dir, &entry.path)?;
+    remove_receipt(install_dir, entry)
+}
+
+/// Whether `entry` was installed from `crate_name`, as its receipt records. A binary without
+/// a receipt is taken to belong to the crate it is named after, since that is the binary
+/// cargox assumes when `--bin` is not given.
+fn is_from_crate(receipts: &[Receipt], entry: &InstalledBinary, crate_name: &str) -> bool {
+    match receipts.iter().find(|receipt| receipt.describes(entry)) {
+        Some(receipt) => receipt.crate_name == crate_name,
+        None => entry.binary == crate_name,
+    }
+}
+
+fn select_versions(installed: Vec<InstalledBinary>, version: &VersionSpec) -> Vec<InstalledBinary> {
+    match version {
+        VersionSpec::Unspecified => installed,
+        VersionSpec::Latest => {
+            let Some(newest) = installed
+                .iter()
+                .filter_map(|entry| entry.version.release())
+                .max()
+                .cloned()
+            else {
+                return vec![];
+            };
+            installed
+                .into_iter()
+                .filter(|entry| entry.version.release() == Some(&newest))
+                .collect()
+        }
+        VersionSpec::Requirement(requirement) => installed
+            .into_iter()
+            .filter(|entry| entry.version.matches(requirement))
+            .collect(),
+        VersionSpec::Dependency(_) => {
+            unreachable!("dependency specs are resolved before selecting versions")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::{test_receipt, write_receipt};
+    use crate::versions::test_installed;
+    use semver::VersionReq;
+    use tempfile::tempdir;
+
+    fn versions(entries: &[InstalledBinary]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| format!("{}-{}", entry.binary, entry.version))
+            .collect()
+    }
+
+    #[test]
+    fn select_versions_honours_spec() {
+        let all = vec![
+            test_installed("rg", "13.0.0"),
+            test_installed("rg", "14.0.0"),
+            test_installed("rg", "14.1.0"),
+        ];
+
+        assert_eq!(
+            select_versions(all.clone(), &VersionSpec::Unspecified).len(),
+            3
+        );
+        assert_eq!(
+            versions(&select_versions(all.clone(), &VersionSpec::Latest)),
+            vec!["rg-14.1.0"]
+        );
+        let req = VersionReq::parse("^14").unwrap();
+        assert_eq!(
+            versions(&select_versions(all, &VersionSpec::Requirement(req))),
+            vec!["rg-14.0.0", "rg-14.1.0"]
+        );
+    }
+
+    #[test]
+    fn entries_are_matched_by_owning_crate() {
+        let receipts = vec![
+            test_receipt("wasm-bindgen-cli", "wasm-bindgen", "0.2.92"),
+            test_receipt("wasm-bindgen-cli", "wasm-bindgen-test-runner", "0.2.92"),
+        ];
+        let all = [
+            test_installed("wasm-bindgen", "0.2.80"),
+            test_installed("wasm-bindgen", "0.2.92"),
+            test_installed("wasm-bindgen-test-runner", "0.2.92"),
+        ];
+        let from = |crate_name: &str| {
+            let entries: Vec<_> = all
+                .iter()
+                .filter(|entry| is_from_crate(&receipts, entry, crate_name))
+                .cloned()
+                .collect();
+            versions(&entries)
+        };
+
+        assert_eq!(
+            from("wasm-bindgen-cli"),
+            vec!["wasm-bindgen-0.2.92", "wasm-bindgen-test-runner-0.2.92"]
+        );
+        // Only the build without a receipt is assumed to come from the crate it is named after
+        assert_eq!(from("wasm-bindgen"), vec!["wasm-bindgen-0.2.80"]);
+    }
+
+    #[test]
+    fn remove_installed_deletes_binary_and_receipt() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("rg-14.1.0");
+        fs::write(&path, "").unwrap();
+        write_receipt(temp.path(), &test_receipt("ripgrep", "rg", "14.1.0")).unwrap();
+
+        let entry = InstalledBinary {
+            path: path.clone(),
+            ..test_installed("rg", "14.1.0")
+        };
+        remove_installed(temp.path(), &entry).unwrap();
+
+        assert!(!path.exists());
+        assert!(all_receipts(temp.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{Context, Result, anyhow};
+use std::env;
+
+use crate::config::load_config;
+use crate::lockfile::Lockfile;
+use crate::manifest::find_manifest;
+use crate::registries::find_registry;
+use crate::registry::{fetch_checksum, fetch_highest_matching_version};
+
+/// Re-resolve the project's pinned tools against the registry and rewrite `cargox.lock`.
+pub fn update_lockfile(tool: Option<&str>) -> Result<()> {
+    let cwd = env::current_dir().context("failed to determine current directory")?;
+    let manifest = find_manifest(&cwd)?.ok_or_else(|| {
+        anyhow!(
+            "no cargox.toml or [workspace.metadata.cargox] found in {} or its parents",
+            cwd.display()
+        )
+    })?;
+
+    let tools: Vec<_> = match tool {
+        Some(name) => {
+            let spec = manifest
+                .tool(name)
+                .ok_or_else(|| anyhow!("{name} is not pinned in {}", manifest.path.display()))?;
+            vec![(name, spec)]
+        }
+        None => manifest
+            .tools
+            .iter()
+            .map(|(name, spec)| (name.as_str(), spec))
+            .collect(),
+    };
+
+    let lockfile_path = manifest.lockfile_path();
+    let mut lockfile = Lockfile::load(&lockfile_path)?;
+    lockfile.retain(|name| manifest.tool(name).is_some());
+    let config = load_config()?;
+
+    for (name, spec) in tools {
+        let registry = spec
+            .registry
+            .as_deref()
+            .map(|registry| find_registry(registry, &cwd, &config))
+            .transpose()?;
+        let version =
+            fetch_highest_matching_version(name, Some(&spec.requirement), registry.as_ref())?;
+        let checksum = fetch_checksum(name, &version, registry.as_ref())?;
+        match lockfile.get(name).map(|locked| locked.version.clone()) {
+            Some(previous) if previous == version => eprintln!("{name} {version} is up to date"),
+            Some(previous) => eprintln!("Updated {name} {previous} -> {version}"),
+            None => eprintln!("Locked {name} {version}"),
+        }
+        lockfile.set(name, &version, spec.registry.as_deref(), Some(&checksum));
+    }
+
+    lockfile.save(&lockfile_path)
+}
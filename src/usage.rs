@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::receipt::read_receipt;
+use crate::versions::InstalledBinary;
+
+/// Record that the versioned binary at `binary_path` is being executed now.
+pub fn record_use(install_dir: &Path, binary_path: &Path) -> Result<()> {
+    let Some(stamp) = stamp_path(install_dir, binary_path) else {
+        return Ok(());
+    };
+    if let Some(parent) = stamp.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    fs::write(&stamp, now.to_string())
+        .with_context(|| format!("failed to write {}", stamp.display()))
+}
+
+/// When the binary was last executed through cargox, falling back to the install time in its
+/// receipt. The binary's mtime is not used: installers preserve the timestamps of the files
+/// they download or copy.
+pub fn last_used(install_dir: &Path, entry: &InstalledBinary) -> Option<SystemTime> {
+    let recorded = stamp_path(install_dir, &entry.path)
+        .and_then(|stamp| fs::read_to_string(stamp).ok())
+        .and_then(|contents| contents.trim().parse::<u64>().ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+    recorded.or_else(|| {
+        read_receipt(install_dir, entry)
+            .ok()
+            .flatten()
+            .map(|receipt| UNIX_EPOCH + Duration::from_secs(receipt.installed_at))
+    })
+}
+
+/// Drop the usage record of a binary that is being removed.
+pub fn forget(install_dir: &Path, binary_path: &Path) -> Result<()> {
+    let Some(stamp) = stamp_path(install_dir, binary_path) else {
+        return Ok(());
+    };
+    match fs::remove_file(&stamp) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).context(format!("failed to remove {}", stamp.display())),
+    }
+}
+
+fn stamp_path(install_dir: &Path, binary_path: &Path) -> Option<PathBuf> {
+    let name = binary_path.file_name()?;
+    Some(install_dir.join("usage").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipt::{Receipt, test_receipt, write_receipt};
+    use crate::versions::test_installed;
+    use tempfile::tempdir;
+
+    fn installed(install_dir: &Path) -> InstalledBinary {
+        InstalledBinary {
+            path: install_dir.join("bin").join("rg-14.1.0"),
+            ..test_installed("rg", "14.1.0")
+        }
+    }
+
+    #[test]
+    fn record_use_updates_last_used() {
+        let temp = tempdir().unwrap();
+        let entry = installed(temp.path());
+
+        assert!(last_used(temp.path(), &entry).is_none());
+
+        record_use(temp.path(), &entry.path).unwrap();
+        let used = last_used(temp.path(), &entry).unwrap();
+        assert!(used.elapsed().unwrap() < Duration::from_secs(60));
+
+        forget(temp.path(), &entry.path).unwrap();
+        assert!(last_used(temp.path(), &entry).is_none());
+    }
+
+    #[test]
+    fn last_used_falls_back_to_install_time() {
+        let temp = tempdir().unwrap();
+        let entry = installed(temp.path());
+        fs::create_dir_all(entry.path.parent().unwrap()).unwrap();
+        fs::write(&entry.path, "").unwrap();
+
+        // A fresh mtime is not evidence of use
+        assert!(last_used(temp.path(), &entry).is_none());
+
+        write_receipt(
+            temp.path(),
+            &Receipt {
+                installed_at: 1_700_000_000,
+                ..test_receipt("ripgrep", "rg", "14.1.0")
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            last_used(temp.path(), &entry),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+    }
+}
@@ -0,0 +1,589 @@
+use anyhow::{Context, Result, anyhow};
+use semver::Version;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle, sleep};
+use std::time::{Duration, Instant};
+
+/// How long a binary may take to answer `--version` before it is considered broken.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much of a binary is read to find its headers, which sit at the start of the file.
+const HEADER_LEN: u64 = 64 * 1024;
+
+/// The executable file formats cargox knows how to check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Elf,
+    MachO,
+    Pe,
+}
+
+impl Format {
+    fn host() -> Self {
+        if cfg!(windows) {
+            Format::Pe
+        } else if cfg!(target_vendor = "apple") {
+            Format::MachO
+        } else {
+            Format::Elf
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Elf => "ELF",
+            Format::MachO => "Mach-O",
+            Format::Pe => "PE",
+        }
+    }
+}
+
+/// The machine identifiers each format uses for an architecture, named as in
+/// `std::env::consts::ARCH`.
+struct Architecture {
+    name: &'static str,
+    elf: u16,
+    mach_o: Option<u32>,
+    pe: Option<u16>,
+}
+
+const ARCHITECTURES: &[Architecture] = &[
+    Architecture {
+        name: "x86_64",
+        elf: 62,
+        mach_o: Some(0x0100_0007),
+        pe: Some(0x8664),
+    },
+    Architecture {
+        name: "aarch64",
+        elf: 183,
+        mach_o: Some(0x0100_000c),
+        pe: Some(0xaa64),
+    },
+    Architecture {
+        name: "x86",
+        elf: 3,
+        mach_o: Some(7),
+        pe: Some(0x014c),
+    },
+    Architecture {
+        name: "arm",
+        elf: 40,
+        mach_o: Some(12),
+        pe: Some(0x01c4),
+    },
+    Architecture {
+        name: "powerpc64",
+        elf: 21,
+        mach_o: Some(0x0100_0012),
+        pe: None,
+    },
+    Architecture {
+        name: "riscv64",
+        elf: 243,
+        mach_o: None,
+        pe: None,
+    },
+    Architecture {
+        name: "s390x",
+        elf: 22,
+        mach_o: None,
+        pe: None,
+    },
+    Architecture {
+        name: "loongarch64",
+        elf: 258,
+        mach_o: None,
+        pe: None,
+    },
+];
+
+/// What a binary's headers say about where it can run.
+#[derive(Debug, PartialEq, Eq)]
+struct Executable {
+    format: Format,
+    /// The machine identifiers it contains code for; universal Mach-O binaries have several.
+    machines: Vec<u32>,
+    /// The dynamic loader an ELF binary asks for, which depends on the libc it was built for.
+    interpreter: Option<String>,
+}
+
+/// Check that the file at `path` is a native executable this machine can run: its format
+/// and architecture must be the host's, and a dynamically linked ELF binary's loader must
+/// exist, which catches binaries built for another libc.
+pub fn check_executable(path: &Path) -> Result<()> {
+    let mut header = vec![];
+    File::open(path)
+        .and_then(|file| file.take(HEADER_LEN).read_to_end(&mut header))
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let executable = parse_header(&header)
+        .with_context(|| format!("{} is not a native executable", path.display()))?;
+    let host = Format::host();
+    if executable.format != host {
+        return Err(anyhow!(
+            "{} is a {} executable, but this platform runs {} executables",
+            path.display(),
+            executable.format.name(),
+            host.name()
+        ));
+    }
+
+    if let Some(host_arch) = ARCHITECTURES
+        .iter()
+        .find(|arch| arch.name == std::env::consts::ARCH)
+    {
+        let machine = match host {
+            Format::Elf => Some(u32::from(host_arch.elf)),
+            Format::MachO => host_arch.mach_o,
+            Format::Pe => host_arch.pe.map(u32::from),
+        };
+        if let Some(machine) = machine
+            && !executable.machines.contains(&machine)
+        {
+            return Err(anyhow!(
+                "{} is built for {}, not {}",
+                path.display(),
+                architecture_names(&executable),
+                host_arch.name
+            ));
+        }
+    }
+
+    if let Some(interpreter) = &executable.interpreter
+        && !Path::new(interpreter).exists()
+    {
+        return Err(anyhow!(
+            "{} needs the dynamic loader {interpreter}, which this system does not have; it may be built for a different libc",
+            path.display()
+        ));
+    }
+
+    Ok(())
+}
+
+fn architecture_names(executable: &Executable) -> String {
+    let names: Vec<String> = executable
+        .machines
+        .iter()
+        .map(|&machine| {
+            ARCHITECTURES
+                .iter()
+                .find(|arch| match executable.format {
+                    Format::Elf => u32::from(arch.elf) == machine,
+                    Format::MachO => arch.mach_o == Some(machine),
+                    Format::Pe => arch.pe.map(u32::from) == Some(machine),
+                })
+                .map_or_else(
+                    || format!("machine {machine:#x}"),
+                    |arch| arch.name.to_owned(),
+                )
+        })
+        .collect();
+    names.join(", ")
+}
+
+fn parse_header(header: &[u8]) -> Result<Executable> {
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => parse_elf(header),
+        [0xcf, 0xfa, 0xed, 0xfe, ..] | [0xce, 0xfa, 0xed, 0xfe, ..] => Ok(Executable {
+            format: Format::MachO,
+            machines: vec![read_u32(header, 4, false)?],
+            interpreter: None,
+        }),
+        [0xca, 0xfe, 0xba, 0xbe, ..] => parse_universal(header),
+        [b'M', b'Z', ..] => parse_pe(header),
+        [b'#', b'!', ..] => Err(anyhow!("it is a script")),
+        _ => Err(anyhow!("its file header is not recognised")),
+    }
+}
+
+fn parse_elf(header: &[u8]) -> Result<Executable> {
+    let is_64 = match header.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(anyhow!("its ELF class is invalid")),
+    };
+    let big_endian = match header.get(5) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => return Err(anyhow!("its ELF byte order is invalid")),
+    };
+    if big_endian != cfg!(target_endian = "big") {
+        return Err(anyhow!("its byte order is not this platform's"));
+    }
+
+    let machine = read_u16(header, 18, big_endian)?;
+    let (phoff, phentsize, phnum) = if is_64 {
+        (
+            read_u64(header, 32, big_endian)?,
+            read_u16(header, 54, big_endian)?,
+            read_u16(header, 56, big_endian)?,
+        )
+    } else {
+        (
+            u64::from(read_u32(header, 28, big_endian)?),
+            read_u16(header, 42, big_endian)?,
+            read_u16(header, 44, big_endian)?,
+        )
+    };
+
+    // PT_INTERP names the dynamic loader. Program headers beyond what was read are
+    // skipped, which only loses the loader check.
+    let mut interpreter = None;
+    for index in 0..u64::from(phnum) {
+        let entry = phoff.saturating_add(index * u64::from(phentsize));
+        let Ok(entry) = usize::try_from(entry) else {
+            break;
+        };
+        let Ok(kind) = read_u32(header, entry, big_endian) else {
+            break;
+        };
+        if kind != 3 {
+            continue;
+        }
+        let (offset, size) = if is_64 {
+            (
+                read_u64(header, entry + 8, big_endian)?,
+                read_u64(header, entry + 32, big_endian)?,
+            )
+        } else {
+            (
+                u64::from(read_u32(header, entry + 4, big_endian)?),
+                u64::from(read_u32(header, entry + 16, big_endian)?),
+            )
+        };
+        interpreter = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(size).ok())
+            .and_then(|(offset, size)| header.get(offset..offset.checked_add(size)?))
+            .map(|bytes| {
+                String::from_utf8_lossy(bytes)
+                    .trim_end_matches('\0')
+                    .to_owned()
+            });
+        break;
+    }
+
+    Ok(Executable {
+        format: Format::Elf,
+        machines: vec![u32::from(machine)],
+        interpreter,
+    })
+}
+
+/// A universal Mach-O binary, which holds code for several architectures.
+fn parse_universal(header: &[u8]) -> Result<Executable> {
+    let count = read_u32(header, 4, true)?;
+    let machines = (0..count as usize)
+        .map(|index| read_u32(header, 8 + index * 20, true))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Executable {
+        format: Format::MachO,
+        machines,
+        interpreter: None,
+    })
+}
+
+fn parse_pe(header: &[u8]) -> Result<Executable> {
+    let offset = read_u32(header, 0x3c, false)? as usize;
+    if header.get(offset..offset.saturating_add(4)) != Some(b"PE\0\0") {
+        return Err(anyhow!("it has a DOS header but no PE header"));
+    }
+    Ok(Executable {
+        format: Format::Pe,
+        machines: vec![u32::from(read_u16(header, offset + 4, false)?)],
+        interpreter: None,
+    })
+}
+
+fn read_bytes<const N: usize>(header: &[u8], offset: usize) -> Result<[u8; N]> {
+    header
+        .get(offset..offset.saturating_add(N))
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("its header is truncated"))
+}
+
+fn read_u16(header: &[u8], offset: usize, big_endian: bool) -> Result<u16> {
+    let bytes = read_bytes(header, offset)?;
+    Ok(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(header: &[u8], offset: usize, big_endian: bool) -> Result<u32> {
+    let bytes = read_bytes(header, offset)?;
+    Ok(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64(header: &[u8], offset: usize, big_endian: bool) -> Result<u64> {
+    let bytes = read_bytes(header, offset)?;
+    Ok(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+/// Run `path --version` and check that `expected` is among the versions it prints. Cargo
+/// subcommands such as `cargo-foo` are run as `cargo-foo foo --version`, as cargo runs them.
+/// Output without a recognisable version, such as `1.2` or a date, is only warned about.
+pub fn check_reported_version(path: &Path, expected: &Version) -> Result<()> {
+    let mut command = Command::new(path);
+    if let Some(subcommand) = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.strip_prefix("cargo-"))
+    {
+        command.arg(subcommand);
+    }
+    let mut child = command
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {}", path.display()))?;
+
+    // Read on threads so that output larger than a pipe holds cannot stall the child.
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let deadline = Instant::now() + VERSION_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() > deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(anyhow!(
+                    "{} --version did not finish within {} seconds",
+                    path.display(),
+                    VERSION_TIMEOUT.as_secs()
+                ));
+            }
+            Ok(None) => sleep(Duration::from_millis(20)),
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err).with_context(|| format!("failed to run {}", path.display()));
+            }
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&stdout),
+        String::from_utf8_lossy(&stderr)
+    );
+    let reported = reported_versions(&text);
+    if reported.contains(expected) {
+        return Ok(());
+    }
+    match reported.first() {
+        Some(version) => Err(anyhow!(
+            "{} reports version {version}, but {expected} was requested",
+            path.display()
+        )),
+        None => {
+            eprintln!(
+                "warning: {} --version did not report a version ({status}); its version was not checked",
+                path.display()
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Read everything from a child's pipe on a new thread.
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+/// Every semver version mentioned in `text`, such as `14.1.0` in `ripgrep 14.1.0 (rev 1)`
+/// or `v0.9.72`.
+fn reported_versions(text: &str) -> Vec<Version> {
+    text.split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '"' | '\''))
+        .filter_map(|word| {
+            let word = word.strip_prefix('v').unwrap_or(word);
+            word.trim_end_matches(['.', ':']).parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn check_executable_accepts_host_binaries() {
+        let current = std::env::current_exe().unwrap();
+        check_executable(&current).unwrap();
+    }
+
+    #[test]
+    fn check_executable_rejects_scripts_and_data() {
+        let temp = tempdir().unwrap();
+        let script = temp.path().join("tool");
+        fs::write(&script, "#!/bin/sh\necho tool 1.0.0\n").unwrap();
+        let err = check_executable(&script).unwrap_err();
+        assert!(format!("{err:#}").contains("is a script"));
+
+        fs::write(&script, "<html>Not Found</html>").unwrap();
+        let err = check_executable(&script).unwrap_err();
+        assert!(format!("{err:#}").contains("not a native executable"));
+    }
+
+    #[test]
+    fn parse_header_reads_universal_and_pe_binaries() {
+        let mut universal = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2];
+        for cputype in [0x0100_0007u32, 0x0100_000c] {
+            universal.extend(cputype.to_be_bytes());
+            universal.extend([0; 16]);
+        }
+        let executable = parse_header(&universal).unwrap();
+        assert_eq!(executable.format, Format::MachO);
+        assert_eq!(executable.machines, [0x0100_0007, 0x0100_000c]);
+
+        let mut pe = vec![0; 0x48];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x44..0x46].copy_from_slice(&0xaa64u16.to_le_bytes());
+        let executable = parse_header(&pe).unwrap();
+        assert_eq!(executable.format, Format::Pe);
+        assert_eq!(executable.machines, [0xaa64]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_executable_rejects_other_architectures_and_loaders() {
+        let temp = tempdir().unwrap();
+        let mut header = vec![];
+        File::open(std::env::current_exe().unwrap())
+            .unwrap()
+            .take(HEADER_LEN)
+            .read_to_end(&mut header)
+            .unwrap();
+
+        let foreign = temp.path().join("foreign");
+        let mut other_arch = header.clone();
+        let machine: u16 = if std::env::consts::ARCH == "s390x" {
+            62
+        } else {
+            22
+        };
+        other_arch[18..20].copy_from_slice(&machine.to_ne_bytes());
+        fs::write(&foreign, &other_arch).unwrap();
+        let err = check_executable(&foreign).unwrap_err();
+        assert!(err.to_string().contains("is built for"), "{err}");
+
+        let Some(interpreter) = parse_header(&header).unwrap().interpreter else {
+            return;
+        };
+        let start = header
+            .windows(interpreter.len())
+            .position(|window| window == interpreter.as_bytes())
+            .unwrap();
+        let missing = "/nonexistent/ld.so";
+        header[start..start + interpreter.len()].fill(0);
+        header[start..start + missing.len()].copy_from_slice(missing.as_bytes());
+        fs::write(&foreign, &header).unwrap();
+        let err = check_executable(&foreign).unwrap_err();
+        assert!(err.to_string().contains("different libc"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_reported_version_compares_with_request() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempdir().unwrap();
+        let tool = temp.path().join("tool");
+        fs::write(&tool, "#!/bin/sh\necho \"tool 1.2.3\"\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+        check_reported_version(&tool, &Version::new(1, 2, 3)).unwrap();
+        let err = check_reported_version(&tool, &Version::new(1, 2, 4)).unwrap_err();
+        assert!(err.to_string().contains("reports version 1.2.3"), "{err}");
+
+        // More output than a pipe buffers must not stall the check until it times out
+        let chatty = temp.path().join("chatty");
+        fs::write(
+            &chatty,
+            "#!/bin/sh\nhead -c 1000000 /dev/zero | tr '\\0' x\necho\necho \"chatty 1.2.3\" >&2\n",
+        )
+        .unwrap();
+        fs::set_permissions(&chatty, fs::Permissions::from_mode(0o755)).unwrap();
+        let started = Instant::now();
+        check_reported_version(&chatty, &Version::new(1, 2, 3)).unwrap();
+        assert!(started.elapsed() < VERSION_TIMEOUT);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_reported_version_runs_cargo_subcommands_as_cargo_does() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempdir().unwrap();
+        let subcommand = temp.path().join("cargo-foo");
+        fs::write(
+            &subcommand,
+            "#!/bin/sh
+if [ \"$1\" = foo ]; then echo \"cargo-foo 1.2.3\"; else echo \"cargo-foo 0.0.1\"; fi
+",
+        )
+        .unwrap();
+        fs::set_permissions(&subcommand, fs::Permissions::from_mode(0o755)).unwrap();
+
+        check_reported_version(&subcommand, &Version::new(1, 2, 3)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_reported_version_accepts_output_without_a_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempdir().unwrap();
+        for (name, output) in [("short", "tool 1.2"), ("dated", "tool 2024-05-01")] {
+            let tool = temp.path().join(name);
+            fs::write(&tool, format!("#!/bin/sh\necho \"{output}\"\n")).unwrap();
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+            check_reported_version(&tool, &Version::new(1, 2, 3)).unwrap();
+        }
+    }
+
+    #[test]
+    fn reported_versions_finds_versions_in_output() {
+        let version = |v: &str| Version::parse(v).unwrap();
+        assert_eq!(
+            reported_versions("ripgrep 14.1.0 (rev e50df40a19)\n+SIMD -AVX\n"),
+            [version("14.1.0")]
+        );
+        assert_eq!(
+            reported_versions("cargo-nextest v0.9.72, built with rustc 1.80.0."),
+            [version("0.9.72"), version("1.80.0")]
+        );
+        assert!(reported_versions("usage: tool [OPTIONS]").is_empty());
+    }
+}
@@ -1,35 +1,205 @@
 use crate::paths::get_install_dir;
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use semver::{Version, VersionReq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Length of the hash abbreviation that identifies a git or local build.
+const HASH_LEN: usize = 12;
+
+/// What distinguishes one installed build of a binary from another: a published release,
+/// a build of a specific git commit, or a build of local sources.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstalledVersion {
+    Release(Version),
+    /// A commit, abbreviated to its first 12 hex digits and shown as `git.<hash>`.
+    Git(String),
+    /// A fingerprint of local sources, abbreviated to 12 hex digits and shown as
+    /// `path.<hash>`.
+    Path(String),
+}
+
+impl InstalledVersion {
+    pub fn git(commit: &str) -> Self {
+        InstalledVersion::Git(abbreviate(commit))
+    }
+
+    pub fn path(fingerprint: &str) -> Self {
+        InstalledVersion::Path(abbreviate(fingerprint))
+    }
+
+    /// The published version, for builds that have one.
+    pub fn release(&self) -> Option<&Version> {
+        match self {
+            InstalledVersion::Release(version) => Some(version),
+            InstalledVersion::Git(_) | InstalledVersion::Path(_) => None,
+        }
+    }
+
+    pub fn matches(&self, requirement: &VersionReq) -> bool {
+        self.release()
+            .is_some_and(|version| requirement.matches(version))
+    }
+}
+
+fn abbreviate(hash: &str) -> String {
+    let hash = hash.to_ascii_lowercase();
+    hash[..hash.len().min(HASH_LEN)].to_owned()
+}
+
+impl From<Version> for InstalledVersion {
+    fn from(version: Version) -> Self {
+        InstalledVersion::Release(version)
+    }
+}
+
+impl fmt::Display for InstalledVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstalledVersion::Release(version) => write!(f, "{version}"),
+            InstalledVersion::Git(commit) => write!(f, "git.{commit}"),
+            InstalledVersion::Path(fingerprint) => write!(f, "path.{fingerprint}"),
+        }
+    }
+}
+
+impl FromStr for InstalledVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let is_hash = |hash: &str| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit());
+        if let Some(commit) = s.strip_prefix("git.") {
+            if !is_hash(commit) {
+                return Err(anyhow!("invalid git build identifier `{s}`"));
+            }
+            return Ok(InstalledVersion::git(commit));
+        }
+        if let Some(fingerprint) = s.strip_prefix("path.") {
+            if !is_hash(fingerprint) {
+                return Err(anyhow!("invalid local build identifier `{s}`"));
+            }
+            return Ok(InstalledVersion::path(fingerprint));
+        }
+        Ok(InstalledVersion::Release(Version::parse(s)?))
+    }
+}
+
+impl Serialize for InstalledVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for InstalledVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct InstalledBinary {
-    pub version: Version,
+    pub binary: String,
+    pub version: InstalledVersion,
+    /// The alternative registry a release was installed from; `None` for crates.io and for
+    /// git and path builds.
+    pub registry: Option<String>,
     pub path: PathBuf,
 }
 
-pub fn versioned_binary_name(binary: &str, version: &Version) -> String {
-    format!("{binary}-{version}")
+impl InstalledBinary {
+    /// The version, followed by the registry it came from unless that is crates.io.
+    pub fn version_label(&self) -> String {
+        match &self.registry {
+            Some(registry) => format!("{} ({registry})", self.version),
+            None => self.version.to_string(),
+        }
+    }
 }
 
-pub fn versioned_binary_path(binary: &str, version: &Version) -> Result<PathBuf> {
-    let bin_dir = ensure_bin_dir()?;
+/// An installed binary for tests, at the relative path `<binary>-<version>`.
+#[cfg(test)]
+pub fn test_installed(binary: &str, version: &str) -> InstalledBinary {
+    InstalledBinary {
+        binary: binary.to_owned(),
+        version: version.parse().unwrap(),
+        registry: None,
+        path: PathBuf::from(format!("{binary}-{version}")),
+    }
+}
+
+/// `<binary>-<version>`, with an `@<registry>` suffix for releases from an alternative
+/// registry so that they never replace the same version from crates.io.
+pub fn versioned_binary_name(
+    binary: &str,
+    version: &InstalledVersion,
+    registry: Option<&str>,
+) -> String {
+    match registry {
+        Some(registry) => format!("{binary}-{version}@{registry}"),
+        None => format!("{binary}-{version}"),
+    }
+}
+
+/// The path of a versioned binary in `bin_dir`.
+pub fn versioned_binary_path(
+    bin_dir: &Path,
+    binary: &str,
+    version: &InstalledVersion,
+    registry: Option<&str>,
+) -> PathBuf {
+    let name = versioned_binary_name(binary, version, registry);
     #[cfg(windows)]
-    let path = bin_dir.join(format!("{}.exe", versioned_binary_name(binary, version)));
+    let path = bin_dir.join(format!("{name}.exe"));
     #[cfg(not(windows))]
-    let path = bin_dir.join(versioned_binary_name(binary, version));
-    Ok(path)
+    let path = bin_dir.join(name);
+    path
+}
+
+/// Split a versioned file name such as `cargo-nextest-0.9.1`, `tool-git.0123456789ab` or
+/// `tool-1.2.0@internal` into the binary name, version and registry. The first `-` that is
+/// followed by a valid version wins, so pre-release versions like `tool-1.0.0-beta.1` are
+/// kept intact.
+pub fn parse_versioned_name(name: &str) -> Option<(String, InstalledVersion, Option<String>)> {
+    let (name, registry) = match name.rsplit_once('@') {
+        Some((_, "")) => return None,
+        Some((name, registry)) => (name, Some(registry.to_owned())),
+        None => (name, None),
+    };
+    name.match_indices('-').find_map(|(idx, _)| {
+        let (binary, version) = (&name[..idx], &name[idx + 1..]);
+        if binary.is_empty() {
+            return None;
+        }
+        version
+            .parse()
+            .ok()
+            .map(|version| (binary.to_owned(), version, registry.clone()))
+    })
+}
+
+/// Every installed version of `binary` from `registry`, where `None` means crates.io and
+/// local builds.
+pub fn list_installed_versions(
+    binary: &str,
+    registry: Option<&str>,
+) -> Result<Vec<InstalledBinary>> {
+    let mut installed = list_all_installed()?;
+    installed.retain(|entry| entry.binary == binary && entry.registry.as_deref() == registry);
+    Ok(installed)
 }
 
-pub fn list_installed_versions(binary: &str) -> Result<Vec<InstalledBinary>> {
+/// Every versioned binary in the install directory, sorted by binary name and version.
+pub fn list_all_installed() -> Result<Vec<InstalledBinary>> {
     let bin_dir = ensure_bin_dir()?;
-    if !bin_dir.exists() {
-        return Ok(vec![]);
-    }
+    installed_in(&bin_dir)
+}
 
-    let entries = match fs::read_dir(&bin_dir) {
+fn installed_in(bin_dir: &Path) -> Result<Vec<InstalledBinary>> {
+    let entries = match fs::read_dir(bin_dir) {
         Ok(entries) => entries,
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
         Err(err) => {
@@ -40,7 +210,6 @@ pub fn list_installed_versions(binary: &str) -> Result<Vec<InstalledBinary>> {
         }
     };
 
-    let prefix = format!("{binary}-");
     let mut installed = Vec::new();
 
     for entry in entries {
@@ -58,35 +227,57 @@ pub fn list_installed_versions(binary: &str) -> Result<Vec<InstalledBinary>> {
         #[cfg(windows)]
         let name = name.strip_suffix(".exe").unwrap_or(name);
 
-        let Some(version_str) = name.strip_prefix(&prefix) else {
-            continue;
-        };
-
-        let Ok(version) = Version::parse(version_str) else {
+        let Some((binary, version, registry)) = parse_versioned_name(name) else {
             continue;
         };
 
-        installed.push(InstalledBinary { version, path });
+        installed.push(InstalledBinary {
+            binary,
+            version,
+            registry,
+            path,
+        });
     }
 
-    installed.sort_by(|a, b| a.version.cmp(&b.version));
+    installed.sort_by(|a, b| {
+        a.binary
+            .cmp(&b.binary)
+            .then(a.version.cmp(&b.version))
+            .then(a.registry.cmp(&b.registry))
+    });
     Ok(installed)
 }
 
 pub fn find_installed_version(
     binary: &str,
+    registry: Option<&str>,
     requirement: &VersionReq,
 ) -> Result<Option<InstalledBinary>> {
-    let installed = list_installed_versions(binary)?;
+    let installed = list_installed_versions(binary, registry)?;
     Ok(installed
         .into_iter()
         .rev()
-        .find(|entry| requirement.matches(&entry.version)))
+        .find(|entry| entry.version.matches(requirement)))
 }
 
-pub fn latest_installed(binary: &str) -> Result<Option<InstalledBinary>> {
-    let mut installed = list_installed_versions(binary)?;
-    Ok(installed.pop())
+pub fn find_exact_version(
+    binary: &str,
+    registry: Option<&str>,
+    version: &InstalledVersion,
+) -> Result<Option<InstalledBinary>> {
+    let installed = list_installed_versions(binary, registry)?;
+    Ok(installed
+        .into_iter()
+        .find(|entry| &entry.version == version))
+}
+
+/// The newest installed release. Git builds are only run when asked for explicitly.
+pub fn latest_installed(binary: &str, registry: Option<&str>) -> Result<Option<InstalledBinary>> {
+    let installed = list_installed_versions(binary, registry)?;
+    Ok(installed
+        .into_iter()
+        .rev()
+        .find(|entry| entry.version.release().is_some()))
 }
 
 pub fn ensure_bin_dir() -> Result<PathBuf> {
@@ -142,16 +333,21 @@ mod tests {
     #[test]
     fn versioned_binary_path_uses_version_suffix() {
         let temp = tempdir().unwrap();
-        let version = Version::parse("1.2.3").unwrap();
+        let version = InstalledVersion::Release(Version::parse("1.2.3").unwrap());
 
-        with_install_dir(temp.path(), || {
-            let path = versioned_binary_path("example", &version).unwrap();
-            let filename = path.file_name().unwrap().to_string_lossy();
-            #[cfg(windows)]
-            assert_eq!(filename, "example-1.2.3.exe");
-            #[cfg(not(windows))]
-            assert_eq!(filename, "example-1.2.3");
-        });
+        let path = versioned_binary_path(temp.path(), "example", &version, None);
+        let filename = path.file_name().unwrap().to_string_lossy();
+        #[cfg(windows)]
+        assert_eq!(filename, "example-1.2.3.exe");
+        #[cfg(not(windows))]
+        assert_eq!(filename, "example-1.2.3");
+
+        let path = versioned_binary_path(temp.path(), "example", &version, Some("internal"));
+        let filename = path.file_name().unwrap().to_string_lossy();
+        #[cfg(windows)]
+        assert_eq!(filename, "example-1.2.3@internal.exe");
+        #[cfg(not(windows))]
+        assert_eq!(filename, "example-1.2.3@internal");
     }
 
     #[test]
@@ -163,10 +359,10 @@ mod tests {
             fs::write(bin_dir.join("tool-0.1.0"), "").unwrap();
             fs::write(bin_dir.join("tool-0.2.0"), "").unwrap();
 
-            let versions = list_installed_versions("tool").unwrap();
+            let versions = list_installed_versions("tool", None).unwrap();
             assert_eq!(versions.len(), 2);
-            assert_eq!(versions[0].version, Version::parse("0.1.0").unwrap());
-            assert_eq!(versions[1].version, Version::parse("0.2.0").unwrap());
+            assert_eq!(versions[0].version.to_string(), "0.1.0");
+            assert_eq!(versions[1].version.to_string(), "0.2.0");
         });
     }
 
@@ -180,8 +376,95 @@ mod tests {
             fs::write(bin_dir.join("util-1.5.0"), "").unwrap();
 
             let req = VersionReq::parse("^1.0").unwrap();
-            let result = find_installed_version("util", &req).unwrap().unwrap();
-            assert_eq!(result.version, Version::parse("1.5.0").unwrap());
+            let result = find_installed_version("util", None, &req).unwrap().unwrap();
+            assert_eq!(result.version.to_string(), "1.5.0");
+        });
+    }
+
+    #[test]
+    fn latest_installed_ignores_git_builds() {
+        let temp = tempdir().unwrap();
+
+        with_install_dir(temp.path(), || {
+            let bin_dir = ensure_bin_dir().unwrap();
+            fs::write(bin_dir.join("tool-1.0.0"), "").unwrap();
+            fs::write(bin_dir.join("tool-git.0123456789ab"), "").unwrap();
+
+            let latest = latest_installed("tool", None).unwrap().unwrap();
+            assert_eq!(latest.version.to_string(), "1.0.0");
+            let git = find_exact_version("tool", None, &InstalledVersion::git("0123456789abcdef"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(git.version.to_string(), "git.0123456789ab");
+        });
+    }
+
+    #[test]
+    fn installed_versions_are_kept_per_registry() {
+        let temp = tempdir().unwrap();
+
+        with_install_dir(temp.path(), || {
+            let bin_dir = ensure_bin_dir().unwrap();
+            fs::write(bin_dir.join("tool-1.2.0"), "").unwrap();
+            fs::write(bin_dir.join("tool-1.2.0@internal"), "").unwrap();
+            let req = VersionReq::parse("^1.2").unwrap();
+
+            let public = find_installed_version("tool", None, &req).unwrap().unwrap();
+            assert_eq!(public.path, bin_dir.join("tool-1.2.0"));
+            let internal = find_installed_version("tool", Some("internal"), &req)
+                .unwrap()
+                .unwrap();
+            assert_eq!(internal.path, bin_dir.join("tool-1.2.0@internal"));
+            assert_eq!(internal.registry.as_deref(), Some("internal"));
+
+            let latest = latest_installed("tool", Some("internal")).unwrap().unwrap();
+            assert_eq!(latest.path, internal.path);
+            assert!(latest_installed("tool", Some("other")).unwrap().is_none());
         });
     }
+
+    #[test]
+    fn parse_versioned_name_handles_hyphens() {
+        let (binary, version, _) = parse_versioned_name("cargo-nextest-0.9.1").unwrap();
+        assert_eq!(binary, "cargo-nextest");
+        assert_eq!(version.to_string(), "0.9.1");
+
+        let (binary, version, _) = parse_versioned_name("tool-1.0.0-beta.1").unwrap();
+        assert_eq!(binary, "tool");
+        assert_eq!(version.to_string(), "1.0.0-beta.1");
+
+        let (binary, version, _) = parse_versioned_name("my-tool-git.0123456789ab").unwrap();
+        assert_eq!(binary, "my-tool");
+        assert_eq!(version, InstalledVersion::Git("0123456789ab".into()));
+
+        let (binary, version, _) = parse_versioned_name("codegen-path.fedcba987654").unwrap();
+        assert_eq!(binary, "codegen");
+        assert_eq!(version, InstalledVersion::Path("fedcba987654".into()));
+
+        let (binary, version, registry) =
+            parse_versioned_name("tool-1.0.0-beta.1@internal").unwrap();
+        assert_eq!(binary, "tool");
+        assert_eq!(version.to_string(), "1.0.0-beta.1");
+        assert_eq!(registry.as_deref(), Some("internal"));
+
+        assert!(parse_versioned_name("rg").is_none());
+        assert!(parse_versioned_name("tool-1.0.0@").is_none());
+        assert!(parse_versioned_name("-1.0.0").is_none());
+    }
+
+    #[test]
+    fn installed_in_groups_binaries() {
+        let temp = tempdir().unwrap();
+        fs::write(temp.path().join("rg-14.1.0"), "").unwrap();
+        fs::write(temp.path().join("bat-0.24.0"), "").unwrap();
+        fs::write(temp.path().join("rg-13.0.0"), "").unwrap();
+        fs::write(temp.path().join("rg"), "").unwrap();
+
+        let installed = installed_in(temp.path()).unwrap();
+        let names: Vec<String> = installed
+            .iter()
+            .map(|entry| format!("{}-{}", entry.binary, entry.version))
+            .collect();
+        assert_eq!(names, vec!["bat-0.24.0", "rg-13.0.0", "rg-14.1.0"]);
+    }
 }
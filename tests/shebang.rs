@@ -0,0 +1,139 @@
+//! Scripts that use cargox as their interpreter. The kernel only runs `#!` lines on Unix.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tempfile::TempDir;
+
+/// A Rust script that prints each of its arguments in brackets, used as the tool that
+/// shebang scripts run.
+const PRINTER: &str = r#"fn main() {
+    for arg in std::env::args().skip(1) {
+        println!("[{arg}]");
+    }
+}
+"#;
+
+struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        let sandbox = Sandbox {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        sandbox.write_script("printer.rs", "", PRINTER);
+        sandbox
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn write_script(&self, name: &str, shebang: &str, body: &str) -> PathBuf {
+        let path = self.path(name);
+        let contents = if shebang.is_empty() {
+            body.to_owned()
+        } else {
+            format!("#!{shebang}\n{body}")
+        };
+        fs::write(&path, contents).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn run(&self, script: &Path, args: &[&str]) -> Output {
+        let output = Command::new(script)
+            .args(args)
+            .env("CARGOX_CACHE_DIR", self.path("cache"))
+            .env("CARGOX_INSTALL_DIR", self.path("install"))
+            .output()
+            .expect("failed to run script");
+        assert!(
+            output.status.success(),
+            "script failed.\nStderr:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output
+    }
+}
+
+fn cargox() -> &'static str {
+    env!("CARGO_BIN_EXE_cargox")
+}
+
+fn printed(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+#[test]
+fn env_split_shebang_runs_rust_script() {
+    let sandbox = Sandbox::new();
+    let script = sandbox.write_script(
+        "hello.rs",
+        &format!("/usr/bin/env -S {} run", cargox()),
+        PRINTER,
+    );
+
+    let output = sandbox.run(&script, &["one", "two words", "--flag"]);
+    assert_eq!(printed(&output), ["[one]", "[two words]", "[--flag]"]);
+}
+
+#[test]
+fn tool_receives_script_path_then_arguments() {
+    let sandbox = Sandbox::new();
+    let printer = sandbox.path("printer.rs");
+    let script = sandbox.write_script(
+        "data.txt",
+        &format!(
+            "/usr/bin/env -S {} run {} --label 'two words'",
+            cargox(),
+            printer.display()
+        ),
+        "payload\n",
+    );
+
+    let output = sandbox.run(&script, &["extra"]);
+    assert_eq!(
+        printed(&output),
+        [
+            "[--label]".to_owned(),
+            "[two words]".to_owned(),
+            format!("[{}]", script.display()),
+            "[extra]".to_owned(),
+        ]
+    );
+}
+
+#[test]
+fn direct_interpreter_line_is_split_by_cargox() {
+    // Without `env -S`, Linux passes `--shebang run <printer> 'two words'` as one argument.
+    let sandbox = Sandbox::new();
+    let printer = sandbox.path("printer.rs");
+    let script = sandbox.write_script(
+        "data.txt",
+        &format!(
+            "{} --shebang run {} 'two words'",
+            cargox(),
+            printer.display()
+        ),
+        "payload\n",
+    );
+
+    let output = sandbox.run(&script, &["extra"]);
+    assert_eq!(
+        printed(&output),
+        [
+            "[two words]".to_owned(),
+            format!("[{}]", script.display()),
+            "[extra]".to_owned(),
+        ]
+    );
+}
@@ -0,0 +1,83 @@
+//! Signals sent to cargox while it waits on an installer.
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// A build script that records the pid of the cargo running it, then blocks until that
+/// cargo goes away.
+const BUILD_SCRIPT: &str = r#"use std::os::unix::process::parent_id;
+
+fn main() {
+    let cargo = parent_id();
+    let marker = env!("CARGO_MANIFEST_DIR").to_owned() + "/building";
+    std::fs::write(&marker, cargo.to_string()).unwrap();
+    while parent_id() == cargo {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+"#;
+
+fn wait_for(what: &str, timeout: Duration, mut done: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(start.elapsed() < timeout, "timed out waiting for {what}");
+        sleep(Duration::from_millis(50));
+    }
+}
+
+fn is_running(pid: &str) -> bool {
+    Command::new("kill")
+        .args(["-0", pid])
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn sigterm_is_forwarded_to_the_installer() {
+    let temp = tempfile::tempdir().unwrap();
+    let package = temp.path().join("slow-build");
+    write(
+        &package.join("Cargo.toml"),
+        "[package]\nname = \"slow-build\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    );
+    write(&package.join("build.rs"), BUILD_SCRIPT);
+    write(&package.join("src/main.rs"), "fn main() {}\n");
+
+    let mut cargox = Command::new(env!("CARGO_BIN_EXE_cargox"))
+        .arg("-q")
+        .arg(format!("path:{}", package.display()))
+        .env("CARGOX_INSTALL_DIR", temp.path().join("install"))
+        .env("CARGOX_CACHE_DIR", temp.path().join("cache"))
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let marker = package.join("building");
+    wait_for("the build script", Duration::from_secs(120), || {
+        fs::read_to_string(&marker).is_ok_and(|pid| !pid.is_empty())
+    });
+    let cargo = fs::read_to_string(&marker).unwrap();
+
+    Command::new("kill")
+        .args(["-TERM", &cargox.id().to_string()])
+        .status()
+        .unwrap();
+    let status = cargox.wait().unwrap();
+
+    assert_eq!(status.signal(), Some(15), "cargox exited with {status}");
+    wait_for("cargo to stop", Duration::from_secs(10), || {
+        !is_running(&cargo)
+    });
+}